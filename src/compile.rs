@@ -0,0 +1,204 @@
+//! Parsing of the logs produced by the JIT compiler (ptxas) during [`crate::cuda::CUDA::compile_jit`].
+
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use crate::cuda_result::CUError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+    Fatal,
+}
+
+/// A single message of the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Line in the PTX source the message refers to.
+    pub line: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Fatal => "fatal",
+        };
+        match self.line {
+            Some(line) => write!(f, "line {line}: {severity}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Parses a line such as `ptxas application ptx input, line 12; error   : Unknown symbol 'x'`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (head, message) = line.split_once(" : ").or_else(|| line.split_once(": "))?;
+        // The severity is the last word of the head, preceded by the location if there is one.
+        let (location, severity) = match head.rsplit_once(';') {
+            Some((location, severity)) => (Some(location), severity),
+            None => (None, head),
+        };
+        let severity = match severity.split_whitespace().last()? {
+            "info" => Severity::Info,
+            "warning" => Severity::Warning,
+            "error" => Severity::Error,
+            "fatal" => Severity::Fatal,
+            _ => return None,
+        };
+        let line = location
+            .and_then(|location| location.rsplit_once("line "))
+            .and_then(|(_, line)| line.trim().parse().ok());
+        Some(Self {
+            line,
+            severity,
+            message: message.trim().to_owned(),
+        })
+    }
+}
+
+/// Parses every diagnostic out of a compiler log.
+pub fn parse_diagnostics(log: &str) -> Vec<Diagnostic> {
+    log.lines().filter_map(Diagnostic::parse).collect()
+}
+
+/// A failed compilation.
+#[derive(Debug)]
+pub struct CompileError {
+    /// The driver error that aborted the compilation.
+    pub error: Box<CUError>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The raw error log.
+    pub log: String,
+}
+
+impl CompileError {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| matches!(d.severity, Severity::Error | Severity::Fatal))
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PTX compilation failed: {}", self.error)?;
+        for diagnostic in self.errors() {
+            write!(f, "\n    {diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Resource usage of a single function, as reported by the compiler.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionReport {
+    pub name: String,
+    pub registers: u32,
+    pub spill_stores: u32,
+    pub spill_loads: u32,
+    /// Stack frame size in bytes.
+    pub stack_frame: u32,
+    /// Static shared memory in bytes.
+    pub shared_memory: u32,
+}
+
+impl FunctionReport {
+    pub fn spills(&self) -> bool {
+        self.spill_stores > 0 || self.spill_loads > 0
+    }
+}
+
+/// Summary of a successful compilation, parsed from the info log.
+#[derive(Debug, Clone, Default)]
+pub struct CompileReport {
+    pub functions: Vec<FunctionReport>,
+    pub compile_time: Option<Duration>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The raw info log.
+    pub log: String,
+}
+
+/// Returns the number preceding `unit` in `text`, e.g. `8` for `unit = "bytes stack frame"` in
+/// `8 bytes stack frame, 0 bytes spill stores`.
+fn value_before(text: &str, unit: &str) -> Option<u32> {
+    let (before, _) = text.split_once(unit)?;
+    before.split_whitespace().last()?.parse().ok()
+}
+
+impl CompileReport {
+    pub fn parse(log: &str) -> Self {
+        let mut report = Self {
+            log: log.to_owned(),
+            diagnostics: parse_diagnostics(log),
+            ..Default::default()
+        };
+        for line in log.lines() {
+            let text = line.trim();
+            let text = text.split_once(" : ").map_or(text, |(_, text)| text.trim());
+            let lower = text.to_lowercase();
+
+            // ptxas announces a function before listing its properties.
+            for prefix in ["Compiling entry function ", "Function properties for "] {
+                if let Some(name) = text.strip_prefix(prefix) {
+                    let name = match name.strip_prefix('\'') {
+                        Some(quoted) => quoted.split('\'').next().unwrap_or_default(),
+                        None => name.trim_end_matches(':'),
+                    }
+                    .to_owned();
+                    if report.functions.last().map(|f| &f.name) != Some(&name) {
+                        report.functions.push(FunctionReport {
+                            name,
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            if let Some(time) = text.strip_prefix("Compile time = ") {
+                report.compile_time = time
+                    .trim_end_matches("ms")
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+            }
+
+            let Some(function) = report.functions.last_mut() else {
+                continue;
+            };
+            if let Some(registers) = value_before(&lower, "registers") {
+                function.registers = registers;
+            }
+            if let Some(stack) =
+                value_before(&lower, "bytes stack frame").or_else(|| value_before(&lower, "stack,"))
+            {
+                function.stack_frame = stack;
+            }
+            if let Some(stores) = value_before(&lower, "bytes spill stores") {
+                function.spill_stores = stores;
+            }
+            if let Some(loads) = value_before(&lower, "bytes spill loads") {
+                function.spill_loads = loads;
+            }
+            if let Some(smem) = value_before(&lower, "bytes smem") {
+                function.shared_memory = smem;
+            }
+        }
+        report
+    }
+
+    /// Whether any function spills registers to local memory.
+    pub fn spills(&self) -> bool {
+        self.functions.iter().any(FunctionReport::spills)
+    }
+}
@@ -1,191 +1,1227 @@
-use std::ffi::{c_char, c_uint, c_void, CStr, CString};
-use std::fmt::Debug;
+use std::env;
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
 use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use derive_more::Deref;
-use dlopen::wrapper::Container;
 use log::{error, trace, warn};
+use tracing_mutex::parkinglot::DebugMutex;
 
+use crate::compile::{parse_diagnostics, CompileError, CompileReport};
 use crate::cuda_api::*;
 use crate::cuda_result::*;
+use crate::hazard::{BufferAccess, Hazards, Submission, Target};
+use crate::loader;
+use crate::properties::DeviceProperties;
+use crate::stream::{PendingCallbacks, Stream};
+use crate::trace::{Trace, TRACE_PATH_ENV};
 
-pub struct Buffer {
+/// Plain data that can be copied between host and device memory byte by byte.
+///
+/// # Safety
+///
+/// Every bit pattern of the size of the type has to be a valid value, and the type must not
+/// contain padding or pointers into host memory.
+pub unsafe trait Pod: Copy + Send + Sync + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Memory for `len` elements of `T` allocated on a [`Device`].
+///
+/// Buffers can be sent to and shared between threads: methods that write to the memory take
+/// `&mut self`, and every call makes the owning device's context current first.
+///
+/// Empty buffers, i.e. of length zero or of a zero-sized `T`, do not allocate any device memory
+/// and have a null device address.
+pub struct Buffer<T: Pod = u8> {
     device: Arc<Device>,
     dptr: *mut c_void,
-    size: usize,
+    len: usize,
+    /// The [`Device::generation`] the buffer was allocated in.
+    generation: u64,
+    /// Accesses by streams, see [`crate::hazard`].
+    hazards: Hazards,
+    _element: PhantomData<T>,
 }
 
+// SAFETY: The device pointer is only an address in the device's context, which can be used and
+// freed from any thread. Writes require `&mut Buffer`, so shared references only read.
+unsafe impl<T: Pod> Send for Buffer<T> {}
+unsafe impl<T: Pod> Sync for Buffer<T> {}
+
 impl Buffer {
-    pub fn create(device: &Arc<Device>, size: usize) -> Self {
+    /// Allocates `size` bytes with unspecified contents.
+    pub fn create(device: &Arc<Device>, size: usize) -> Result<Self> {
+        Self::uninit(device, size)
+    }
+}
+
+impl<T: Pod> Buffer<T> {
+    /// Allocates `len` elements with unspecified contents.
+    pub fn uninit(device: &Arc<Device>, len: usize) -> Result<Self> {
+        let size = len
+            .checked_mul(size_of::<T>())
+            .ok_or(CUError::AllocationOverflow {
+                len,
+                element: size_of::<T>(),
+            })?;
         let mut dptr: *mut c_void = null_mut();
-        unsafe { device.cuda.cuMemAlloc(&mut dptr, size as _) };
-        Self {
+        if size > 0 {
+            device.reserve_memory(size)?;
+            match device.call(|cuda| unsafe { cuda.cuMemAlloc(&mut dptr, size as _) }) {
+                Ok(()) => device.commit_memory(),
+                Err(err) => {
+                    device.cancel_memory(size);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(Self {
             device: device.clone(),
             dptr,
-            size,
+            len,
+            generation: device.generation(),
+            hazards: Hazards::default(),
+            _element: PhantomData,
+        })
+    }
+    /// Allocates `len` elements with all bytes set to zero.
+    pub fn zeroed(device: &Arc<Device>, len: usize) -> Result<Self> {
+        let buffer = Self::uninit(device, len)?;
+        if buffer.size() > 0 {
+            device.call(|cuda| unsafe { cuda.cuMemsetD8(buffer.dptr, 0, buffer.size() as _) })?;
+            let submission = Submission::record(device, Target::new(device, None))?;
+            buffer.hazards.after(&submission, true);
+        }
+        Ok(buffer)
+    }
+    /// Allocates a buffer holding a copy of `src`.
+    pub fn from_slice(device: &Arc<Device>, src: &[T]) -> Result<Self> {
+        let mut buffer = Self::uninit(device, src.len())?;
+        buffer.copy_from_slice(src)?;
+        Ok(buffer)
+    }
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        self.len * size_of::<T>()
+    }
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+    /// The device address of the allocation.
+    pub fn as_device_ptr(&self) -> *mut c_void {
+        self.dptr
+    }
+    /// The device address, stored for as long as the buffer lives, e.g. for kernel parameters.
+    pub(crate) fn device_ptr_ref(&self) -> &*mut c_void {
+        &self.dptr
+    }
+    /// Whether the allocation still exists, i.e. the device has not been recovered since.
+    pub fn is_valid(&self) -> bool {
+        self.generation == self.device.generation()
+    }
+    fn check_valid(&self) -> Result<()> {
+        if !self.is_valid() {
+            return Err(CUError::Invalidated(self.device.id));
+        }
+        Ok(())
+    }
+    /// Checks that `len` elements starting at element `offset` lie inside the buffer and
+    /// returns the device address of the first one.
+    fn range_ptr(&self, offset: usize, len: usize) -> Result<*mut c_void> {
+        let element = size_of::<T>();
+        let out_of_bounds = || CUError::OutOfBounds {
+            offset: offset.saturating_mul(element),
+            len: len.saturating_mul(element),
+            size: self.size(),
+        };
+        let end = offset.checked_add(len).ok_or_else(out_of_bounds)?;
+        if end > self.len {
+            return Err(out_of_bounds());
+        }
+        Ok(self.dptr.wrapping_byte_add(offset * element))
+    }
+    /// A view of the elements in `range`, e.g. `buffer.slice(10..20)`.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Result<BufferSlice<'_, T>> {
+        let (offset, len) = resolve_range::<T>(range, self.len)?;
+        Ok(BufferSlice::new(self, offset, len))
+    }
+    /// A mutable view of the elements in `range`.
+    pub fn slice_mut(&mut self, range: impl RangeBounds<usize>) -> Result<BufferSliceMut<'_, T>> {
+        let (offset, len) = resolve_range::<T>(range, self.len)?;
+        Ok(BufferSliceMut::new(self, offset, len))
+    }
+    /// Splits the buffer into two mutable views at element `mid`.
+    pub fn split_at_mut(
+        &mut self,
+        mid: usize,
+    ) -> Result<(BufferSliceMut<'_, T>, BufferSliceMut<'_, T>)> {
+        self.slice_mut(..).and_then(|slice| slice.split_at(mid))
+    }
+    /// Uploads `src`, which has to have the same length as the buffer.
+    pub fn copy_from_slice(&mut self, src: &[T]) -> Result<()> {
+        check_size(self.size(), size_of_val(src))?;
+        self.upload(0, src, None)
+    }
+    /// Uploads `src` to the elements starting at `offset`.
+    pub fn copy_from_slice_at(&mut self, offset: usize, src: &[T]) -> Result<()> {
+        self.upload(offset, src, None)
+    }
+    /// Downloads the buffer into `dst`, which has to have the same length as the buffer.
+    pub fn copy_to_slice(&self, dst: &mut [T]) -> Result<()> {
+        check_size(self.size(), size_of_val(dst))?;
+        self.copy_to_slice_at(0, dst)
+    }
+    /// Downloads the elements starting at `offset` into `dst`.
+    pub fn copy_to_slice_at(&self, offset: usize, dst: &mut [T]) -> Result<()> {
+        // SAFETY: `dst` is valid for writes of its length.
+        unsafe { self.download(offset, dst.as_mut_ptr(), dst.len(), None) }
+    }
+    /// Downloads the whole buffer.
+    pub fn to_vec(&self) -> Result<Vec<T>> {
+        self.download_vec(0, self.len)
+    }
+    /// Copies `src`, which has to have the same length and device, into this buffer.
+    ///
+    /// Buffers of other devices are copied with [`Buffer::copy_from_peer`].
+    pub fn copy_from_buffer(&mut self, src: &Buffer<T>) -> Result<()> {
+        if !Arc::ptr_eq(&self.device, &src.device) {
+            return Err(CUError::BufferDeviceMismatch {
+                dst: self.device.id,
+                src: src.device.id,
+            });
+        }
+        check_size(self.size(), src.size())?;
+        self.copy_range(0, src, 0, self.len, None)
+    }
+    /// Copies `src`, which may live on another device, into this buffer.
+    ///
+    /// The copy goes directly between the devices if [`Device::enable_peer_access`] has been
+    /// called, otherwise the driver stages it through host memory.
+    pub fn copy_from_peer(&mut self, src: &Buffer<T>) -> Result<()> {
+        check_size(self.size(), src.size())?;
+        self.copy_range(0, src, 0, self.len, None)
+    }
+    /// Sets every element to `value`.
+    pub fn fill(&mut self, value: T) -> Result<()> {
+        self.fill_range(0, self.len, value, None)
+    }
+
+    /// Uploads `src`, which has to have the same length as the buffer, in order with the work
+    /// on `stream`.
+    ///
+    /// Host memory is pageable, so the driver has staged `src` when this returns.
+    pub fn copy_from_slice_async(&mut self, src: &[T], stream: &Stream) -> Result<()> {
+        check_size(self.size(), size_of_val(src))?;
+        self.upload(0, src, Some(stream))
+    }
+    /// Downloads the buffer into `dst`, which has to have the same length, once the work on
+    /// `stream` before it has completed.
+    ///
+    /// Host memory is pageable, so the copy has completed when this returns.
+    pub fn copy_to_slice_async(&self, dst: &mut [T], stream: &Stream) -> Result<()> {
+        check_size(self.size(), size_of_val(dst))?;
+        // SAFETY: `dst` is valid for writes of its length.
+        unsafe { self.download(0, dst.as_mut_ptr(), dst.len(), Some(stream)) }
+    }
+    /// Copies `src`, which has to have the same length, in order with the work on `stream`.
+    pub fn copy_from_buffer_async(&mut self, src: &Buffer<T>, stream: &Stream) -> Result<()> {
+        check_size(self.size(), src.size())?;
+        self.copy_range(0, src, 0, self.len, Some(stream))
+    }
+    /// Sets every element to `value` in order with the work on `stream`.
+    pub fn fill_async(&mut self, value: T, stream: &Stream) -> Result<()> {
+        self.fill_range(0, self.len, value, Some(stream))
+    }
+
+    // The following write through `&self`, callers ensure exclusive access to the range.
+    // Without a stream they run on the legacy default stream, which is synchronous for
+    // copies to and from host memory. Work is ordered after conflicting work on other streams.
+
+    fn upload(&self, offset: usize, src: &[T], stream: Option<&Stream>) -> Result<()> {
+        self.check_valid()?;
+        let handle = self.stream_handle(stream)?;
+        let dst = self.range_ptr(offset, src.len())?;
+        let size = size_of_val(src);
+        if size == 0 {
+            return Ok(());
         }
+        let target = Target::new(&self.device, stream);
+        self.hazards.before(&self.device, target, true)?;
+        let src = src.as_ptr() as *const c_void;
+        self.device.call(|cuda| unsafe {
+            match handle {
+                Some(stream) => cuda.cuMemcpyAsync(dst, src, size as _, stream),
+                None => cuda.cuMemcpy(dst, src, size as _),
+            }
+        })?;
+        let submission = Submission::record(&self.device, target)?;
+        self.hazards.after(&submission, true);
+        Ok(())
     }
-    pub fn copy_from_slice(&mut self, src: &[u8]) {
+    /// # Safety
+    ///
+    /// `dst` has to be valid for writes of `len` elements.
+    unsafe fn download(
+        &self,
+        offset: usize,
+        dst: *mut T,
+        len: usize,
+        stream: Option<&Stream>,
+    ) -> Result<()> {
+        self.check_valid()?;
+        let handle = self.stream_handle(stream)?;
+        let src = self.range_ptr(offset, len)?;
+        let size = len * size_of::<T>();
+        if size == 0 {
+            return Ok(());
+        }
+        let target = Target::new(&self.device, stream);
+        self.hazards.before(&self.device, target, false)?;
+        let dst = dst as *mut c_void;
+        self.device.call(|cuda| match handle {
+            Some(stream) => cuda.cuMemcpyAsync(dst, src, size as _, stream),
+            None => cuda.cuMemcpy(dst, src, size as _),
+        })?;
+        let submission = Submission::record(&self.device, target)?;
+        self.hazards.after(&submission, false);
+        Ok(())
+    }
+    fn download_vec(&self, offset: usize, len: usize) -> Result<Vec<T>> {
+        let mut vec = Vec::with_capacity(len);
+        // SAFETY: The capacity is valid for writes of `len` elements, which are initialized
+        // afterwards since every bit pattern is a valid `T`.
         unsafe {
-            self.device
-                .cuda
-                .cuMemcpy(self.dptr, src.as_ptr() as *const c_void, src.len() as _);
+            self.download(offset, vec.as_mut_ptr(), len, None)?;
+            vec.set_len(len);
+        }
+        Ok(vec)
+    }
+    /// Copies `len` elements of `src` starting at `src_offset` to `offset`, going through
+    /// [`Buffer::copy_from_peer`]'s path if `src` lives on another device.
+    fn copy_range(
+        &self,
+        offset: usize,
+        src: &Buffer<T>,
+        src_offset: usize,
+        len: usize,
+        stream: Option<&Stream>,
+    ) -> Result<()> {
+        self.check_valid()?;
+        src.check_valid()?;
+        let handle = self.stream_handle(stream)?;
+        let dst_ptr = self.range_ptr(offset, len)?;
+        let src_ptr = src.range_ptr(src_offset, len)?;
+        let size = len * size_of::<T>();
+        if size == 0 {
+            return Ok(());
+        }
+        src.device.check_poisoned()?;
+        let target = Target::new(&self.device, stream);
+        src.hazards.before(&self.device, target, false)?;
+        self.hazards.before(&self.device, target, true)?;
+        let (dst_context, src_context) = (self.device.context, src.device.context);
+        let same_device = Arc::ptr_eq(&self.device, &src.device);
+        self.device.call(|cuda| unsafe {
+            match (handle, same_device) {
+                (Some(stream), true) => cuda.cuMemcpyAsync(dst_ptr, src_ptr, size as _, stream),
+                (None, true) => cuda.cuMemcpy(dst_ptr, src_ptr, size as _),
+                (Some(stream), false) => cuda.cuMemcpyPeerAsync(
+                    dst_ptr,
+                    dst_context,
+                    src_ptr,
+                    src_context,
+                    size as _,
+                    stream,
+                ),
+                (None, false) => {
+                    cuda.cuMemcpyPeer(dst_ptr, dst_context, src_ptr, src_context, size as _)
+                }
+            }
+        })?;
+        let submission = Submission::record(&self.device, target)?;
+        src.hazards.after(&submission, false);
+        self.hazards.after(&submission, true);
+        Ok(())
+    }
+    /// Uses the memset matching the size of `T`, and uploads copies of `value` otherwise.
+    fn fill_range(
+        &self,
+        offset: usize,
+        len: usize,
+        value: T,
+        stream: Option<&Stream>,
+    ) -> Result<()> {
+        self.check_valid()?;
+        let handle = self.stream_handle(stream)?;
+        let dst = self.range_ptr(offset, len)?;
+        if len * size_of::<T>() == 0 {
+            return Ok(());
         }
+        let target = Target::new(&self.device, stream);
+        self.hazards.before(&self.device, target, true)?;
+        // SAFETY: The sizes match, and `T` has no padding.
+        self.device.call(|cuda| unsafe {
+            match (size_of::<T>(), handle) {
+                (1, Some(stream)) => {
+                    cuda.cuMemsetD8Async(dst, mem::transmute_copy(&value), len as _, stream)
+                }
+                (1, None) => cuda.cuMemsetD8(dst, mem::transmute_copy(&value), len as _),
+                (2, Some(stream)) => {
+                    cuda.cuMemsetD16Async(dst, mem::transmute_copy(&value), len as _, stream)
+                }
+                (2, None) => cuda.cuMemsetD16(dst, mem::transmute_copy(&value), len as _),
+                (4, Some(stream)) => {
+                    cuda.cuMemsetD32Async(dst, mem::transmute_copy(&value), len as _, stream)
+                }
+                (4, None) => cuda.cuMemsetD32(dst, mem::transmute_copy(&value), len as _),
+                (_, handle) => {
+                    // Pageable memory is staged before the call returns, even on a stream.
+                    let values = vec![value; len];
+                    let src = values.as_ptr() as *const c_void;
+                    let size = size_of_val(values.as_slice());
+                    match handle {
+                        Some(stream) => cuda.cuMemcpyAsync(dst, src, size as _, stream),
+                        None => cuda.cuMemcpy(dst, src, size as _),
+                    }
+                }
+            }
+        })?;
+        let submission = Submission::record(&self.device, target)?;
+        self.hazards.after(&submission, true);
+        Ok(())
     }
+    fn stream_handle(&self, stream: Option<&Stream>) -> Result<Option<CUstream>> {
+        stream
+            .map(|stream| stream.handle_for(&self.device))
+            .transpose()
+    }
+    /// How a kernel accesses the buffer when it is passed as an argument.
+    pub(crate) fn access(&self, write: bool) -> Option<BufferAccess<'_>> {
+        (!self.is_empty()).then_some(BufferAccess {
+            hazards: &self.hazards,
+            write,
+            device: &self.device,
+            generation: self.generation,
+        })
+    }
+}
+
+/// Turns `range` into the offset and length of the elements it covers in a buffer or view of
+/// `len` elements, with unbounded ends at `0` and `len`.
+fn resolve_range<T>(range: impl RangeBounds<usize>, len: usize) -> Result<(usize, usize)> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.saturating_add(1),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if start > end || end > len {
+        return Err(CUError::OutOfBounds {
+            offset: start.saturating_mul(size_of::<T>()),
+            len: end.saturating_sub(start).saturating_mul(size_of::<T>()),
+            size: len * size_of::<T>(),
+        });
+    }
+    Ok((start, end - start))
 }
 
-impl Drop for Buffer {
+fn check_size(dst: usize, src: usize) -> Result<()> {
+    if src != dst {
+        return Err(CUError::SizeMismatch { dst, src });
+    }
+    Ok(())
+}
+
+impl<T: Pod> Drop for Buffer<T> {
     fn drop(&mut self) {
+        // Allocations of an earlier generation have been destroyed by the reset already.
+        if self.is_valid() && !self.is_empty() {
+            let _ = self
+                .device
+                .call(|cuda| unsafe { cuda.cuMemFree(self.dptr) });
+            self.device.release_memory(self.size());
+        }
+    }
+}
+
+/// A view of a range of a [`Buffer`], see [`Buffer::slice`].
+#[derive(Clone, Copy)]
+pub struct BufferSlice<'a, T: Pod = u8> {
+    buffer: &'a Buffer<T>,
+    offset: usize,
+    len: usize,
+    /// Device address of the first element, stored for kernel parameters.
+    dptr: *mut c_void,
+}
+
+/// A mutable view of a range of a [`Buffer`], see [`Buffer::slice_mut`].
+///
+/// Views created by [`BufferSliceMut::split_at`] never overlap, so each can be written to
+/// independently.
+pub struct BufferSliceMut<'a, T: Pod = u8> {
+    slice: BufferSlice<'a, T>,
+    _buffer: PhantomData<&'a mut Buffer<T>>,
+}
+
+// SAFETY: Like `&Buffer` and `&mut Buffer`, the device pointer is derived from the buffer.
+unsafe impl<T: Pod> Send for BufferSlice<'_, T> {}
+unsafe impl<T: Pod> Sync for BufferSlice<'_, T> {}
+unsafe impl<T: Pod> Send for BufferSliceMut<'_, T> {}
+unsafe impl<T: Pod> Sync for BufferSliceMut<'_, T> {}
+
+impl<'a, T: Pod> BufferSlice<'a, T> {
+    fn new(buffer: &'a Buffer<T>, offset: usize, len: usize) -> Self {
+        Self {
+            buffer,
+            offset,
+            len,
+            dptr: buffer.dptr.wrapping_byte_add(offset * size_of::<T>()),
+        }
+    }
+    pub fn buffer(&self) -> &'a Buffer<T> {
+        self.buffer
+    }
+    /// Index of the first element in the buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        self.len * size_of::<T>()
+    }
+    /// The device address of the first element.
+    pub fn as_device_ptr(&self) -> *mut c_void {
+        self.dptr
+    }
+    /// The device address, stored for as long as the view lives, e.g. for kernel parameters.
+    pub(crate) fn device_ptr_ref(&self) -> &*mut c_void {
+        &self.dptr
+    }
+    /// A view of `range` relative to this view.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Result<BufferSlice<'a, T>> {
+        let (offset, len) = resolve_range::<T>(range, self.len)?;
+        Ok(Self::new(self.buffer, self.offset + offset, len))
+    }
+    /// Downloads the view into `dst`, which has to have the same length.
+    pub fn copy_to_slice(&self, dst: &mut [T]) -> Result<()> {
+        check_size(self.size(), size_of_val(dst))?;
+        self.buffer.copy_to_slice_at(self.offset, dst)
+    }
+    /// Downloads the view into `dst` once the work on `stream` before it has completed, see
+    /// [`Buffer::copy_to_slice_async`].
+    pub fn copy_to_slice_async(&self, dst: &mut [T], stream: &Stream) -> Result<()> {
+        check_size(self.size(), size_of_val(dst))?;
+        // SAFETY: `dst` is valid for writes of its length.
         unsafe {
-            self.device.cuda.cuMemFree(self.dptr);
+            self.buffer
+                .download(self.offset, dst.as_mut_ptr(), dst.len(), Some(stream))
         }
     }
+    /// Downloads the view.
+    pub fn to_vec(&self) -> Result<Vec<T>> {
+        self.buffer.download_vec(self.offset, self.len)
+    }
 }
 
-pub struct Device {
-    pub cuda: Arc<CUDA>,
-    pub context: CUcontext,
+impl<'a, T: Pod> BufferSliceMut<'a, T> {
+    fn new(buffer: &'a mut Buffer<T>, offset: usize, len: usize) -> Self {
+        Self {
+            slice: BufferSlice::new(buffer, offset, len),
+            _buffer: PhantomData,
+        }
+    }
+    /// Reborrows the view as read-only.
+    pub fn as_slice(&self) -> BufferSlice<'_, T> {
+        self.slice
+    }
+    pub fn offset(&self) -> usize {
+        self.slice.offset
+    }
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.slice.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.slice.is_empty()
+    }
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        self.slice.size()
+    }
+    /// The device address of the first element.
+    pub fn as_device_ptr(&self) -> *mut c_void {
+        self.slice.dptr
+    }
+    pub(crate) fn device_ptr_ref(&self) -> &*mut c_void {
+        &self.slice.dptr
+    }
+    /// A mutable view of `range` relative to this view.
+    pub fn slice_mut(&mut self, range: impl RangeBounds<usize>) -> Result<BufferSliceMut<'_, T>> {
+        Ok(BufferSliceMut {
+            slice: self.slice.slice(range)?,
+            _buffer: PhantomData,
+        })
+    }
+    /// Splits the view into two non-overlapping views at element `mid`.
+    pub fn split_at(self, mid: usize) -> Result<(Self, Self)> {
+        resolve_range::<T>(..mid, self.slice.len)?;
+        let BufferSlice {
+            buffer,
+            offset,
+            len,
+            ..
+        } = self.slice;
+        let part = |offset, len| Self {
+            slice: BufferSlice::new(buffer, offset, len),
+            _buffer: PhantomData,
+        };
+        Ok((part(offset, mid), part(offset + mid, len - mid)))
+    }
+    /// Uploads `src`, which has to have the same length as the view.
+    pub fn copy_from_slice(&mut self, src: &[T]) -> Result<()> {
+        check_size(self.size(), size_of_val(src))?;
+        self.slice.buffer.upload(self.slice.offset, src, None)
+    }
+    /// Uploads `src` in order with the work on `stream`, see [`Buffer::copy_from_slice_async`].
+    pub fn copy_from_slice_async(&mut self, src: &[T], stream: &Stream) -> Result<()> {
+        check_size(self.size(), size_of_val(src))?;
+        self.slice
+            .buffer
+            .upload(self.slice.offset, src, Some(stream))
+    }
+    /// Copies `src`, which has to have the same length and may live on another device.
+    pub fn copy_from(&mut self, src: &BufferSlice<'_, T>) -> Result<()> {
+        check_size(self.size(), src.size())?;
+        self.slice
+            .buffer
+            .copy_range(self.slice.offset, src.buffer, src.offset, src.len, None)
+    }
+    /// Copies `src`, which has to have the same length, in order with the work on `stream`.
+    pub fn copy_from_async(&mut self, src: &BufferSlice<'_, T>, stream: &Stream) -> Result<()> {
+        check_size(self.size(), src.size())?;
+        self.slice.buffer.copy_range(
+            self.slice.offset,
+            src.buffer,
+            src.offset,
+            src.len,
+            Some(stream),
+        )
+    }
+    /// Downloads the view into `dst`, which has to have the same length.
+    pub fn copy_to_slice(&self, dst: &mut [T]) -> Result<()> {
+        self.slice.copy_to_slice(dst)
+    }
+    pub fn to_vec(&self) -> Result<Vec<T>> {
+        self.slice.to_vec()
+    }
+    /// Sets every element of the view to `value`.
+    pub fn fill(&mut self, value: T) -> Result<()> {
+        self.slice
+            .buffer
+            .fill_range(self.slice.offset, self.slice.len, value, None)
+    }
+    /// Sets every element of the view to `value` in order with the work on `stream`.
+    pub fn fill_async(&mut self, value: T, stream: &Stream) -> Result<()> {
+        self.slice
+            .buffer
+            .fill_range(self.slice.offset, self.slice.len, value, Some(stream))
+    }
+}
 
-    // info
+/// Identity of a device, queried without creating a context, see [`CUDA::devices`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
     pub id: i32,
+    pub name: String,
+    pub uuid: CUuuid,
+    pub pci_domain_id: i32,
     pub pci_bus_id: i32,
-    pub pci_dom_id: i32,
-    pub pci_dev_id: i32,
-    pub num_sm: i32,
-    pub unified_addr: i32,
-    pub shared_memory_bytes: i32,
-    pub cc_minor: i32,
+    pub pci_device_id: i32,
     pub cc_major: i32,
+    pub cc_minor: i32,
+    pub num_sm: i32,
     pub mem_total: u64,
-    pub name: String,
 }
 
-impl Device {
-    pub fn create(cuda: &Arc<CUDA>, id: i32) -> Result<Self> {
-        assert!(id < cuda.device_count);
-        let mut pci_bus_id = 0;
-        let mut pci_dom_id = 0;
-        let mut pci_dev_id = 0;
-        let mut num_sm = 0;
-        let mut unified_addr = 0;
-        let mut shared_memory_bytes = 0;
-        let mut cc_minor = 0;
-        let mut cc_major = 0;
-        let mut memory_pool = 0;
+impl DeviceInfo {
+    fn query(cuda: &CUDA, id: i32) -> Result<Self> {
+        let mut name = [0u8; 256];
+        let mut uuid = CUuuid::default();
         let mut mem_total = 0;
-
-        let mut context: CUcontext = null();
-        let mut name = vec![0; 256];
-
-        unsafe { cuda.cuDevicePrimaryCtxRetain(&mut context, id).check()? };
-
         unsafe {
-            cuda.cuDeviceGetName(name.as_ptr() as *mut i8, 256, id)
+            cuda.cuDeviceGetName(name.as_mut_ptr() as *mut c_char, name.len() as _, id)
                 .check()?;
+            cuda.cuDeviceGetUuid(&mut uuid, id).check()?;
+            cuda.cuDeviceTotalMem(&mut mem_total, id).check()?;
         }
-        let name = String::from_utf8(name).unwrap();
-
-        unsafe {
-            cuda.cuDeviceGetAttribute(
-                &mut pci_bus_id,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_BUS_ID as i32,
-                id,
-            )
-            .check()?;
-            cuda.cuDeviceGetAttribute(
-                &mut pci_dev_id,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_DEVICE_ID as i32,
-                id,
-            )
-            .check();
-            cuda.cuDeviceGetAttribute(
-                &mut pci_dom_id,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_DOMAIN_ID as i32,
-                id,
-            )
-            .check()?;
-            cuda.cuDeviceGetAttribute(
-                &mut num_sm,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT as i32,
-                id,
-            )
-            .check()?;
-            cuda.cuDeviceGetAttribute(
-                &mut unified_addr,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_UNIFIED_ADDRESSING as i32,
-                id,
-            )
-            .check()?;
-            cuda.cuDeviceGetAttribute(
-                &mut shared_memory_bytes,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN as i32,
+        let name = CStr::from_bytes_until_nul(&name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self {
+            id,
+            name,
+            uuid,
+            pci_domain_id: cuda
+                .device_attribute(id, CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_DOMAIN_ID)?,
+            pci_bus_id: cuda.device_attribute(id, CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_BUS_ID)?,
+            pci_device_id: cuda
+                .device_attribute(id, CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_DEVICE_ID)?,
+            cc_major: cuda.device_attribute(
                 id,
-            )
-            .check()?;
-            cuda.cuDeviceGetAttribute(
-                &mut cc_minor,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MINOR as i32,
+                CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MAJOR,
+            )?,
+            cc_minor: cuda.device_attribute(
                 id,
-            )
-            .check()?;
-            cuda.cuDeviceGetAttribute(
-                &mut cc_major,
-                CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MAJOR as i32,
-                id,
-            )
-            .check()?;
-            cuda.cuDeviceTotalMem(&mut mem_total, id).check()?;
-        };
+                CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MINOR,
+            )?,
+            num_sm: cuda
+                .device_attribute(id, CUAttribute::CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT)?,
+            mem_total,
+        })
+    }
+
+    /// The PCI address in the form `0000:01:00.0`.
+    pub fn pci_address(&self) -> String {
+        format!(
+            "{:04x}:{:02x}:{:02x}.0",
+            self.pci_domain_id, self.pci_bus_id, self.pci_device_id
+        )
+    }
+
+    /// The UUID in the form `GPU-xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` used by `nvidia-smi` and
+    /// `CUDA_VISIBLE_DEVICES`.
+    pub fn uuid_string(&self) -> String {
+        let hex: String = self.uuid.bytes.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "GPU-{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+/// Parses a PCI address such as `0000:01:00.0` or `01:00.0` into domain, bus and device.
+fn parse_pci_address(address: &str) -> Option<(i32, i32, i32)> {
+    let hex = |part: &str| i32::from_str_radix(part, 16).ok();
+    let address = address.trim();
+    let (address, _function) = address.rsplit_once('.').unwrap_or((address, "0"));
+    let mut parts = address.rsplit(':');
+    let device = hex(parts.next()?)?;
+    let bus = hex(parts.next()?)?;
+    let domain = parts.next().map_or(Some(0), hex)?;
+    parts.next().is_none().then_some((domain, bus, device))
+}
 
-        trace!("Found CUDA Device {name}: PCI_ID {pci_bus_id}, {pci_dev_id}, {pci_dom_id}, compute cap. {cc_major}.{cc_minor}, {num_sm} SMs w/ {shared_memory_bytes}bytes shared mem, {mem_total}bytes global mem.");
+/// How a host thread waits for the device, e.g. in [`Device::synchronize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scheduling {
+    /// Spin if there are more contexts than logical CPUs, yield otherwise.
+    #[default]
+    Auto,
+    /// Busy-wait, which has the lowest latency but occupies a CPU core.
+    Spin,
+    /// Yield the thread while waiting.
+    Yield,
+    /// Block the thread on a synchronization primitive.
+    BlockingSync,
+}
+
+impl Scheduling {
+    pub fn flags(self) -> c_uint {
+        match self {
+            Scheduling::Auto => CU_CTX_SCHED_AUTO,
+            Scheduling::Spin => CU_CTX_SCHED_SPIN,
+            Scheduling::Yield => CU_CTX_SCHED_YIELD,
+            Scheduling::BlockingSync => CU_CTX_SCHED_BLOCKING_SYNC,
+        }
+    }
 
-        Ok(Device {
+    pub fn from_flags(flags: c_uint) -> Self {
+        match flags & CU_CTX_SCHED_MASK {
+            CU_CTX_SCHED_SPIN => Scheduling::Spin,
+            CU_CTX_SCHED_YIELD => Scheduling::Yield,
+            CU_CTX_SCHED_BLOCKING_SYNC => Scheduling::BlockingSync,
+            _ => Scheduling::Auto,
+        }
+    }
+}
+
+/// State of a primary context as reported by `cuDevicePrimaryCtxGetState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimaryContextState {
+    /// The `CU_CTX_*` flags the context is (or will be) created with.
+    pub flags: c_uint,
+    /// Whether the context is retained by anyone, in this process.
+    pub active: bool,
+}
+
+impl PrimaryContextState {
+    pub fn scheduling(&self) -> Scheduling {
+        Scheduling::from_flags(self.flags)
+    }
+}
+
+/// Settings applied to the primary context by [`Device::create_with_config`].
+///
+/// Settings left at `None` keep the value currently in effect. The primary context is shared
+/// by all users of the device in this process, so changing it affects them as well.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// Set through `cuDevicePrimaryCtxSetFlags` before the context is retained.
+    pub scheduling: Option<Scheduling>,
+    /// Stack size in bytes of each GPU thread.
+    pub stack_size: Option<usize>,
+    /// Size in bytes of the buffer for `printf` in kernels.
+    pub printf_fifo_size: Option<usize>,
+    /// Size in bytes of the heap for `malloc` in kernels.
+    pub malloc_heap_size: Option<usize>,
+    /// Maximum number of outstanding device runtime launches.
+    pub pending_launch_count: Option<usize>,
+    /// L2 cache fetch granularity in bytes.
+    pub max_l2_fetch_granularity: Option<usize>,
+}
+
+impl DeviceConfig {
+    fn limits(&self) -> impl Iterator<Item = (CUlimit, usize)> {
+        [
+            (CUlimit::CU_LIMIT_STACK_SIZE, self.stack_size),
+            (CUlimit::CU_LIMIT_PRINTF_FIFO_SIZE, self.printf_fifo_size),
+            (CUlimit::CU_LIMIT_MALLOC_HEAP_SIZE, self.malloc_heap_size),
+            (
+                CUlimit::CU_LIMIT_DEV_RUNTIME_PENDING_LAUNCH_COUNT,
+                self.pending_launch_count,
+            ),
+            (
+                CUlimit::CU_LIMIT_MAX_L2_FETCH_GRANULARITY,
+                self.max_l2_fetch_granularity,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(limit, value)| Some((limit, value?)))
+    }
+}
+
+/// A device together with its retained primary context.
+///
+/// Devices can be sent to and shared between threads, typically behind an [`Arc`]. The driver
+/// lets several threads use a context at once, and each call makes it current on the calling
+/// thread for its duration, see [`Device::activate`]. The state tracked by the device itself is
+/// behind locks and atomics.
+pub struct Device {
+    pub cuda: Arc<CUDA>,
+    pub context: CUcontext,
+    pub id: i32,
+    pub properties: DeviceProperties,
+
+    /// The sticky error that left the context unusable, see [`Device::recover`].
+    poison: DebugMutex<Option<CUresult>>,
+    /// Incremented whenever the context is reset, invalidating everything allocated in it.
+    generation: AtomicU64,
+    memory: DebugMutex<MemoryStats>,
+    /// Callbacks queued with [`crate::stream::Stream::on_complete`].
+    pub(crate) callbacks: PendingCallbacks,
+    /// The scheduling from the [`DeviceConfig`], re-applied by [`Device::recover`].
+    scheduling: Option<Scheduling>,
+    /// Limits set through [`Device::set_limit`], re-applied by [`Device::recover`].
+    limits: DebugMutex<Vec<(CUlimit, usize)>>,
+}
+
+// SAFETY: The context is an opaque handle the driver allows to be current on several threads,
+// and it is only made current for the duration of a call on the calling thread.
+unsafe impl Send for Device {}
+unsafe impl Sync for Device {}
+
+impl Device {
+    pub fn create(cuda: &Arc<CUDA>, id: i32) -> Result<Self> {
+        Self::create_with_config(cuda, id, &DeviceConfig::default())
+    }
+
+    /// Retains the primary context of device `id` and applies `config` to it.
+    pub fn create_with_config(cuda: &Arc<CUDA>, id: i32, config: &DeviceConfig) -> Result<Self> {
+        let properties = cuda.device_properties(id)?;
+
+        if let Some(scheduling) = config.scheduling {
+            cuda.set_scheduling(id, scheduling)?;
+        }
+
+        let mut context: CUcontext = null();
+        unsafe { cuda.cuDevicePrimaryCtxRetain(&mut context, id).check()? };
+
+        trace!(
+            "Found CUDA Device {}: PCI {}, compute cap. {}.{}, {} SMs w/ {}bytes shared mem, {}bytes global mem.",
+            properties.name,
+            properties.pci_address(),
+            properties.cc_major,
+            properties.cc_minor,
+            properties.num_sm,
+            properties.shared_memory_per_block_optin,
+            properties.mem_total
+        );
+
+        let device = Device {
             cuda: cuda.clone(),
             context,
             id,
-            pci_bus_id,
-            pci_dom_id,
-            pci_dev_id,
-            num_sm,
-            unified_addr,
-            shared_memory_bytes,
-            cc_minor,
-            cc_major,
-            mem_total,
-            name,
+            properties,
+            poison: DebugMutex::new(None),
+            generation: AtomicU64::new(0),
+            memory: DebugMutex::new(MemoryStats::default()),
+            callbacks: PendingCallbacks::default(),
+            scheduling: config.scheduling,
+            limits: DebugMutex::new(Vec::new()),
+        };
+        for (limit, value) in config.limits() {
+            device.set_limit(limit, value)?;
+        }
+        Ok(device)
+    }
+
+    pub fn primary_context_state(&self) -> Result<PrimaryContextState> {
+        self.cuda.primary_context_state(self.id)
+    }
+
+    /// Waits until all work submitted to the device has completed.
+    pub fn synchronize(&self) -> Result<()> {
+        self.call(|cuda| unsafe { cuda.cuCtxSynchronize() })
+    }
+
+    /// Reads a limit of the context, see [`CUlimit`].
+    pub fn limit(&self, limit: CUlimit) -> Result<usize> {
+        let mut value = 0;
+        self.call(|cuda| unsafe { cuda.cuCtxGetLimit(&mut value, limit as i32) })?;
+        Ok(value as usize)
+    }
+
+    /// Changes a limit of the context, see [`CUlimit`].
+    /// The driver may round the value, [`Device::limit`] returns the value in effect.
+    pub fn set_limit(&self, limit: CUlimit, value: usize) -> Result<()> {
+        self.call(|cuda| unsafe { cuda.cuCtxSetLimit(limit as i32, value as _) })?;
+        trace!("Set {limit:?} of device {} to {value}", self.id);
+        let mut limits = self.limits.lock();
+        limits.retain(|&(set, _)| set != limit);
+        limits.push((limit, value));
+        Ok(())
+    }
+
+    /// Makes the primary context of this device current on the calling thread until the
+    /// returned guard is dropped.
+    pub fn activate(&self) -> Result<ContextGuard<'_>> {
+        unsafe { self.cuda.cuCtxPushCurrent(self.context).check()? };
+        Ok(ContextGuard {
+            device: self,
+            _not_send: PhantomData,
         })
     }
+
+    /// Makes a driver call on behalf of this device, with its context current.
+    ///
+    /// Calls are rejected once the device is poisoned, and sticky errors poison it.
+    #[track_caller]
+    pub fn call<'a>(&'a self, f: impl FnOnce(&'a CUDA) -> ApiResult<'a>) -> Result<()> {
+        self.check_poisoned()?;
+        let _context = self.activate()?;
+        self.check(f(&self.cuda))
+    }
+
+    /// Checks the result of a call made on behalf of this device, poisoning it on sticky errors.
+    #[track_caller]
+    pub(crate) fn check(&self, result: ApiResult<'_>) -> Result<()> {
+        let result = result.check();
+        if let Err(err) = &result {
+            if let Some(sticky) = err.result().filter(|result| result.is_sticky()) {
+                error!(
+                    "Device {} is poisoned by the sticky error {sticky:?}, it has to be recovered before it can be used again!",
+                    self.id
+                );
+                *self.poison.lock() = Some(sticky);
+            }
+        }
+        result
+    }
+
+    /// Lets this device access the memory of `peer`, e.g. to copy between them directly.
+    ///
+    /// Access is one-directional, and enabling it again has no effect.
+    pub fn enable_peer_access(&self, peer: &Device) -> Result<()> {
+        if peer.id == self.id {
+            return Ok(());
+        }
+        if !self.cuda.can_access_peer(self.id, peer.id)? {
+            return Err(CUError::PeerAccessUnsupported(self.id, peer.id));
+        }
+        self.check_poisoned()?;
+        let _context = self.activate()?;
+        let result = unsafe { self.cuda.cuCtxEnablePeerAccess(peer.context, 0) };
+        if result.result() == Ok(CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED) {
+            trace!(
+                "Peer access from device {} to {} is enabled already",
+                self.id,
+                peer.id
+            );
+            return Ok(());
+        }
+        self.check(result)?;
+        trace!("Enabled peer access from device {} to {}", self.id, peer.id);
+        Ok(())
+    }
+
+    /// Compiles PTX into a cubin in the context of this device, see [`CUDA::compile_jit`].
+    pub fn compile_jit(&self, buf: &mut str) -> Result<(Vec<u8>, CompileReport)> {
+        self.check_poisoned()?;
+        let _context = self.activate()?;
+        self.cuda.compile_jit(buf)
+    }
+
+    /// The sticky error the device is poisoned by, if any.
+    pub fn poisoned(&self) -> Option<CUresult> {
+        *self.poison.lock()
+    }
+
+    pub fn check_poisoned(&self) -> Result<()> {
+        match self.poisoned() {
+            Some(result) => Err(CUError::Poisoned(self.id, result)),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts the resets of the primary context. Buffers and modules of an older generation no
+    /// longer exist.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Memory allocated through [`Buffer`]s on this device.
+    pub fn memory_stats(&self) -> MemoryStats {
+        *self.memory.lock()
+    }
+
+    /// Free and total memory of the device as reported by the driver, in bytes.
+    pub fn memory_info(&self) -> Result<(usize, usize)> {
+        let (mut free, mut total) = (0, 0);
+        self.call(|cuda| unsafe { cuda.cuMemGetInfo(&mut free, &mut total) })?;
+        Ok((free as usize, total as usize))
+    }
+
+    /// Limits the memory that [`Buffer`]s may allocate on this device, or lifts the limit.
+    ///
+    /// Allocations beyond the limit fail with [`CUError::OutOfMemory`], existing ones are kept
+    /// even if they exceed a new limit.
+    pub fn set_memory_limit(&self, limit: Option<usize>) {
+        self.memory.lock().limit = limit;
+    }
+
+    /// Starts tracking the peak usage anew from the current usage.
+    pub fn reset_peak_memory(&self) {
+        let mut memory = self.memory.lock();
+        memory.peak = memory.allocated;
+    }
+
+    /// Counts `size` bytes as allocated before the driver is asked for them, so that concurrent
+    /// allocations respect the limit. Followed by [`Device::commit_memory`] or
+    /// [`Device::cancel_memory`].
+    fn reserve_memory(&self, size: usize) -> Result<()> {
+        let mut memory = self.memory.lock();
+        let limit = memory.limit.unwrap_or(usize::MAX);
+        match memory.allocated.checked_add(size) {
+            Some(allocated) if allocated <= limit => {
+                memory.allocated = allocated;
+                Ok(())
+            }
+            _ => Err(CUError::OutOfMemory {
+                device: self.id,
+                requested: size,
+                allocated: memory.allocated,
+                limit,
+            }),
+        }
+    }
+
+    /// Accounts for a reserved allocation that the driver has made.
+    fn commit_memory(&self) {
+        let mut memory = self.memory.lock();
+        memory.peak = memory.peak.max(memory.allocated);
+        memory.allocations += 1;
+    }
+
+    /// Returns a reservation that the driver could not allocate.
+    fn cancel_memory(&self, size: usize) {
+        self.memory.lock().allocated -= size;
+    }
+
+    fn release_memory(&self, size: usize) {
+        let mut memory = self.memory.lock();
+        memory.allocated -= size;
+        memory.allocations -= 1;
+    }
+
+    /// Resets the primary context after a sticky error.
+    ///
+    /// This destroys all allocations and modules of the context, so every [`Buffer`] created
+    /// before is invalidated and rejects further use. The scheduling and limits set on the
+    /// device are applied again.
+    pub fn recover(&self) -> Result<()> {
+        self.callbacks.discard_after(|| {
+            unsafe { self.cuda.cuDevicePrimaryCtxReset(self.id).check()? };
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            Ok(())
+        })?;
+        *self.poison.lock() = None;
+        let mut memory = self.memory.lock();
+        memory.allocated = 0;
+        memory.allocations = 0;
+        drop(memory);
+        if let Some(scheduling) = self.scheduling {
+            self.cuda.set_scheduling(self.id, scheduling)?;
+        }
+        let limits = self.limits.lock().clone();
+        for (limit, value) in limits {
+            self.set_limit(limit, value)?;
+        }
+        trace!("Recovered device {}", self.id);
+        Ok(())
+    }
+}
+
+/// Keeps the context of a [`Device`] current on the thread that activated it, see
+/// [`Device::activate`].
+///
+/// Contexts are current per thread, so the guard cannot be sent to another thread.
+pub struct ContextGuard<'a> {
+    device: &'a Device,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard<'_> {
+    fn drop(&mut self) {
+        let mut context: CUcontext = null();
+        let popped = unsafe { self.device.cuda.cuCtxPopCurrent(&mut context).check() };
+        match popped {
+            Ok(()) if context != self.device.context => error!(
+                "Popped a foreign context while deactivating device {}, contexts were not released in order!",
+                self.device.id
+            ),
+            Ok(()) => {}
+            Err(err) => error!("Could not deactivate device {}: {err}", self.device.id),
+        }
+    }
 }
 
 impl Drop for Device {
     fn drop(&mut self) {
-        unsafe {
-            self.cuda
-                .cuDevicePrimaryCtxRelease(self.id)
-                .check()
-                .unwrap()
-        };
+        // Releasing the context may destroy it along with callbacks that have not run. A context
+        // that cannot be synchronized is unusable and will not run them either.
+        if !self.callbacks.is_empty() && self.synchronize().is_err() {
+            self.callbacks.discard();
+        }
+        if let Err(err) = unsafe { self.cuda.cuDevicePrimaryCtxRelease(self.id).check() } {
+            error!("Could not release device {}: {err}", self.id);
+        }
     }
 }
 
+/// Memory allocated through [`Buffer`]s on a device, see [`Device::memory_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Bytes currently allocated.
+    pub allocated: usize,
+    /// Most bytes allocated at once since creation or [`Device::reset_peak_memory`].
+    pub peak: usize,
+    /// Number of live allocations.
+    pub allocations: usize,
+    /// The limit set by [`Device::set_memory_limit`].
+    pub limit: Option<usize>,
+}
+
+/// Optional driver features, depending on the driver version and the entry points it exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Stream ordered allocation through `cuMemAllocAsync` and `cuMemFreeAsync` (CUDA 11.2).
+    pub async_alloc: bool,
+}
+
+/// Splits a version in the encoding of `cuDriverGetVersion` into major and minor version.
+fn split_version(version: i32) -> (i32, i32) {
+    (version / 1000, (version % 1000) / 10)
+}
+
+/// The loaded driver.
+///
+/// Thread-safe, as the driver API itself is, so it is usually shared behind an [`Arc`].
 #[derive(Deref)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct CUDA {
     #[deref]
-    api: Container<CudaApi>,
+    api: Api,
     version: (i32, i32),
     device_count: i32,
+    capabilities: Capabilities,
 }
 
 impl CUDA {
+    /// Loads the driver from the first location found by [`loader::candidates`].
     pub fn create() -> Result<Self> {
-        let cuda = unsafe { Container::<CudaApi>::load("/usr/lib64/libcuda.so").unwrap() };
+        Self::load(None)
+    }
+
+    /// Loads the driver from `path`, falling back to the default search locations.
+    /// `path` may either point at the library itself or at the directory containing it.
+    pub fn create_with_path(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load(Some(path.as_ref()))
+    }
+
+    fn load(path: Option<&Path>) -> Result<Self> {
+        let (_, api) = loader::load(path)?;
+        Self::with_driver(api)
+    }
+
+    /// Initializes CUDA on top of an arbitrary driver backend, such as [`crate::fake::FakeDriver`].
+    pub fn with_driver(driver: impl Driver + 'static) -> Result<Self> {
+        let cuda = Api::new(Box::new(driver));
+        if let Some(path) = env::var_os(TRACE_PATH_ENV).filter(|path| !path.is_empty()) {
+            trace!("Tracing driver calls to {}", Path::new(&path).display());
+            cuda.set_trace(Some(Arc::new(Trace::new())));
+        }
 
         unsafe { cuda.cuInit(0).check()? };
 
         let mut device_count = 0;
         unsafe {
-            cuda.cuDeviceGetCount(&mut device_count);
+            cuda.cuDeviceGetCount(&mut device_count).check()?;
         }
 
         trace!("Device Count: {device_count}");
@@ -197,10 +1233,9 @@ impl CUDA {
 
         let mut cuda_version = 0;
         unsafe {
-            cuda.cuDriverGetVersion(&mut cuda_version);
+            cuda.cuDriverGetVersion(&mut cuda_version).check()?;
         }
-        let cuda_version_major = cuda_version / 1000;
-        let cuda_version_minor = (cuda_version % 1000) / 10;
+        let (cuda_version_major, cuda_version_minor) = split_version(cuda_version);
 
         trace!("Cuda version: {cuda_version_major}.{cuda_version_minor}");
 
@@ -209,18 +1244,232 @@ impl CUDA {
             return Err(CUError::CUDAVersion);
         }
 
-        Ok(Self {
+        let mut cuda = Self {
             api: cuda,
             version: (cuda_version_major, cuda_version_minor),
             device_count,
+            capabilities: Capabilities { async_alloc: false },
+        };
+        cuda.capabilities = Capabilities {
+            async_alloc: cuda.supports("cuMemAllocAsync") && cuda.supports("cuMemFreeAsync"),
+        };
+        trace!("Capabilities: {:?}", cuda.capabilities);
+
+        Ok(cuda)
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// The driver version as major and minor version, e.g. `(12, 2)`.
+    pub fn driver_version(&self) -> (i32, i32) {
+        self.version
+    }
+
+    pub fn device_count(&self) -> i32 {
+        self.device_count
+    }
+
+    /// Queries `attribute` of the device `id`.
+    pub fn device_attribute(&self, id: i32, attribute: CUAttribute) -> Result<i32> {
+        let mut value = 0;
+        unsafe {
+            self.cuDeviceGetAttribute(&mut value, attribute as i32, id)
+                .check()?
+        };
+        Ok(value)
+    }
+
+    /// Describes every device, in ordinal order, without creating contexts.
+    pub fn devices(&self) -> impl Iterator<Item = Result<DeviceInfo>> + '_ {
+        (0..self.device_count).map(|id| DeviceInfo::query(self, id))
+    }
+
+    /// Returns the first device that satisfies `predicate`.
+    fn find_device(
+        &self,
+        description: impl FnOnce() -> String,
+        mut predicate: impl FnMut(&DeviceInfo) -> bool,
+    ) -> Result<DeviceInfo> {
+        for info in self.devices() {
+            let info = info?;
+            if predicate(&info) {
+                return Ok(info);
+            }
+        }
+        Err(CUError::NoMatchingDevice(description()))
+    }
+
+    pub fn device_by_ordinal(&self, id: i32) -> Result<DeviceInfo> {
+        if !(0..self.device_count).contains(&id) {
+            return Err(CUError::NoMatchingDevice(format!("ordinal {id}")));
+        }
+        DeviceInfo::query(self, id)
+    }
+
+    /// Whether `device` can access the memory of `peer`.
+    pub fn can_access_peer(&self, device: i32, peer: i32) -> Result<bool> {
+        let mut can_access = 0;
+        unsafe {
+            self.cuDeviceCanAccessPeer(&mut can_access, device, peer)
+                .check()?
+        };
+        Ok(can_access != 0)
+    }
+
+    /// Returns which devices can access each other's memory: `matrix[device][peer]`.
+    /// Devices are considered to access themselves.
+    pub fn peer_access_matrix(&self) -> Result<Vec<Vec<bool>>> {
+        (0..self.device_count)
+            .map(|device| {
+                (0..self.device_count)
+                    .map(|peer| Ok(device == peer || self.can_access_peer(device, peer)?))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the flags and whether the primary context of device `id` is active.
+    pub fn primary_context_state(&self, id: i32) -> Result<PrimaryContextState> {
+        let mut flags = 0;
+        let mut active = 0;
+        unsafe {
+            self.cuDevicePrimaryCtxGetState(id, &mut flags, &mut active)
+                .check()?
+        };
+        Ok(PrimaryContextState {
+            flags,
+            active: active != 0,
+        })
+    }
+
+    /// Changes how host threads wait for device `id`, keeping the other flags of its primary
+    /// context.
+    fn set_scheduling(&self, id: i32, scheduling: Scheduling) -> Result<()> {
+        let state = self.primary_context_state(id)?;
+        let flags = (state.flags & !CU_CTX_SCHED_MASK) | scheduling.flags();
+        unsafe { self.cuDevicePrimaryCtxSetFlags(id, flags).check()? };
+        trace!("Set the scheduling of device {id} to {scheduling:?}");
+        Ok(())
+    }
+
+    /// Queries all properties of the device `id` without creating a context.
+    pub fn device_properties(&self, id: i32) -> Result<DeviceProperties> {
+        DeviceProperties::query(self, self.device_by_ordinal(id)?)
+    }
+
+    /// Returns the device at the PCI address `address`, e.g. `0000:01:00.0` or `01:00.0`.
+    pub fn device_by_pci_bus_id(&self, address: &str) -> Result<DeviceInfo> {
+        let description = || format!("PCI bus id {address}");
+        let Some(pci) = parse_pci_address(address) else {
+            return Err(CUError::NoMatchingDevice(description()));
+        };
+        self.find_device(description, |info| {
+            (info.pci_domain_id, info.pci_bus_id, info.pci_device_id) == pci
         })
     }
 
-    pub fn compile_jit(&self, buf: &mut str) -> Result<()> {
+    /// Returns the first device whose name contains `pattern`, ignoring case.
+    pub fn device_by_name(&self, pattern: &str) -> Result<DeviceInfo> {
+        let pattern = pattern.to_lowercase();
+        self.find_device(
+            || format!("name {pattern:?}"),
+            |info| info.name.to_lowercase().contains(&pattern),
+        )
+    }
+
+    /// Returns the device with the most global memory, the first one if several are equal.
+    pub fn device_with_most_memory(&self) -> Result<DeviceInfo> {
+        let mut best: Option<DeviceInfo> = None;
+        for info in self.devices() {
+            let info = info?;
+            if best
+                .as_ref()
+                .is_none_or(|best| info.mem_total > best.mem_total)
+            {
+                best = Some(info);
+            }
+        }
+        best.ok_or(CUError::NoDevice)
+    }
+
+    /// Selects devices with a comma separated list in the format of `CUDA_VISIBLE_DEVICES`.
+    ///
+    /// Entries are either ordinals or (prefixes of) UUIDs such as `GPU-8932f937`. Like the
+    /// driver, parsing stops at the first entry that matches no device or matches several.
+    /// Devices listed more than once are only returned once.
+    pub fn select_devices(&self, selector: &str) -> Result<Vec<DeviceInfo>> {
+        let devices = self.devices().collect::<Result<Vec<_>>>()?;
+        let mut selected: Vec<DeviceInfo> = vec![];
+        for entry in selector.split(',').map(str::trim) {
+            let matches: Vec<_> = match entry.parse::<i32>() {
+                Ok(id) => devices.iter().filter(|info| info.id == id).collect(),
+                Err(_)
+                    if entry
+                        .get(..4)
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("GPU-")) =>
+                {
+                    let prefix = entry.to_lowercase();
+                    devices
+                        .iter()
+                        .filter(|info| info.uuid_string().to_lowercase().starts_with(&prefix))
+                        .collect()
+                }
+                Err(_) => vec![],
+            };
+            let [info] = matches[..] else {
+                if !entry.is_empty() {
+                    warn!("Device selector entry {entry:?} matches no unique device, ignoring the rest of {selector:?}");
+                }
+                break;
+            };
+            if !selected.contains(info) {
+                selected.push(info.clone());
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Starts recording every driver call made through this instance and returns the recording.
+    /// Keeps the current recording if tracing is already enabled.
+    pub fn enable_trace(&self) -> Arc<Trace> {
+        self.api.trace_or_insert_with(|| Arc::new(Trace::new()))
+    }
+
+    /// Stops recording driver calls and returns the recording, if there was one.
+    pub fn disable_trace(&self) -> Option<Arc<Trace>> {
+        self.api.set_trace(None)
+    }
+
+    /// Whether the driver entry point `name` is exported and recent enough to be used.
+    pub fn supports(&self, name: &str) -> bool {
+        self.driver().has_entry_point(name)
+            && CudaApi::since(name).is_none_or(|since| self.version >= split_version(since))
+    }
+
+    /// Returns [`CUError::Unsupported`] unless [`CUDA::supports`] the entry point `name`.
+    pub fn require(&self, name: &'static str) -> Result<()> {
+        if self.supports(name) {
+            return Ok(());
+        }
+        Err(CUError::Unsupported {
+            function: name,
+            version: self.version,
+            required: CudaApi::since(name).map(split_version),
+        })
+    }
+
+    /// Compiles PTX into a cubin.
+    ///
+    /// Returns the cubin together with the resource usage reported by the compiler, or a
+    /// [`CUError::Compile`] with the parsed diagnostics if compilation fails.
+    /// Requires a current context, use [`Device::compile_jit`] to provide one.
+    pub fn compile_jit(&self, buf: &mut str) -> Result<(Vec<u8>, CompileReport)> {
         trace!("Compiling ptx");
-        const log_size: usize = 16384;
-        let mut error_log = [0 as u8; log_size];
-        let mut info_log = [0 as u8; log_size];
+        const LOG_SIZE: usize = 16384;
+        let mut error_log = [0u8; LOG_SIZE];
+        let mut info_log = [0u8; LOG_SIZE];
         let mut opts = [
             CU_JIT_OPTIMIZATION_LEVEL,
             CU_JIT_LOG_VERBOSE,
@@ -232,14 +1481,14 @@ impl CUDA {
             CU_JIT_GENERATE_DEBUG_INFO,
         ];
         let mut opt_vals = [
-            4 as *mut c_void,
-            1 as *mut c_void,
-            info_log.as_ptr() as *mut c_void,
-            log_size as c_uint as *mut c_void,
-            error_log.as_ptr() as *mut c_void,
-            log_size as c_uint as *mut c_void,
-            0 as *mut c_void,
-            0 as *mut c_void,
+            4 as c_uint as *mut c_void,
+            1 as c_uint as *mut c_void,
+            info_log.as_mut_ptr() as *mut c_void,
+            LOG_SIZE as c_uint as *mut c_void,
+            error_log.as_mut_ptr() as *mut c_void,
+            LOG_SIZE as c_uint as *mut c_void,
+            null_mut(),
+            null_mut(),
         ];
         let mut link_state: CUlinkState = std::ptr::null();
         unsafe {
@@ -252,7 +1501,7 @@ impl CUDA {
             .check()?;
         }
 
-        let rt = unsafe {
+        let cubin = unsafe {
             self.cuLinkAddData(
                 link_state,
                 CU_JIT_INPUT_PTX,
@@ -264,13 +1513,58 @@ impl CUDA {
                 null_mut(),
             )
             .check()
-            .or_else(|e| {
-                error!("compilation failed with the error: {:?}", unsafe {
-                    CStr::from_bytes_until_nul(&error_log)
-                });
-                Err(e)
-            })?;
+            .and_then(|_| {
+                let mut cubin: *mut c_void = null_mut();
+                let mut size = 0;
+                self.cuLinkComplete(link_state, &mut cubin, &mut size)
+                    .check()?;
+                // The cubin is owned by the link state.
+                Ok(std::slice::from_raw_parts(cubin as *const u8, size as usize).to_vec())
+            })
         };
-        todo!()
+        let destroyed = unsafe { self.cuLinkDestroy(link_state).check() };
+
+        // A failed compilation is reported over a failed clean up, which has been logged.
+        let cubin = cubin.map_err(|err| {
+            let log = log_to_string(&error_log);
+            error!("compilation failed with the error: {log}");
+            CUError::Compile(CompileError {
+                error: Box::new(err),
+                diagnostics: parse_diagnostics(&log),
+                log,
+            })
+        })?;
+        destroyed?;
+
+        let report = CompileReport::parse(&log_to_string(&info_log));
+        for function in report.functions.iter().filter(|f| f.spills()) {
+            warn!(
+                "Function {} spills registers: {} bytes stores, {} bytes loads",
+                function.name, function.spill_stores, function.spill_loads
+            );
+        }
+        trace!("Compiled ptx: {report:?}");
+        Ok((cubin, report))
+    }
+}
+
+impl Drop for CUDA {
+    fn drop(&mut self) {
+        let (Some(path), Some(trace)) = (env::var_os(TRACE_PATH_ENV), self.api.trace()) else {
+            return;
+        };
+        if let Err(err) = trace.write_chrome_trace(&path) {
+            warn!(
+                "Could not write driver trace to {}: {err}",
+                Path::new(&path).display()
+            );
+        }
     }
 }
+
+/// Reads a NUL terminated log written by the JIT compiler.
+fn log_to_string(log: &[u8]) -> String {
+    CStr::from_bytes_until_nul(log)
+        .map(|log| log.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(log).into_owned())
+}
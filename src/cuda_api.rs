@@ -1,8 +1,17 @@
 use log::error;
-use std::ffi::{c_char, c_float, c_int, c_uchar, c_uint, c_ulong, c_ushort, c_void};
+use std::ffi::{c_char, c_float, c_int, c_uchar, c_uint, c_ulong, c_ushort, c_void, CStr, OsStr};
+use std::panic::Location;
+use std::ptr::null;
+use std::sync::Arc;
+use std::time::Instant;
 
-use dlopen::wrapper::{Container, WrapperApi};
-use dlopen_derive::WrapperApi;
+use dlopen::raw::Library;
+use num_enum::TryFromPrimitive;
+use tracing_mutex::parkinglot::DebugMutex;
+
+use crate::cuda_result::{ApiError, CUError, UnknownResultError};
+use crate::loader::{Resolution, Resolver, SymbolSource};
+use crate::trace::Trace;
 
 #[repr(C)]
 pub struct CUctx_st {
@@ -49,8 +58,57 @@ pub type CUdevice = c_int;
 pub type CUdeviceptr = *const c_void;
 pub type CUjit_option = c_int;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-#[repr(C)]
+impl CUresult {
+    /// Whether the error leaves the context in an unusable state.
+    ///
+    /// Sticky errors are reported by every later call in the same context, which has to be
+    /// reset before the device can be used again.
+    pub fn is_sticky(self) -> bool {
+        matches!(
+            self,
+            CUresult::CUDA_ERROR_ECC_UNCORRECTABLE
+                | CUresult::CUDA_ERROR_NVLINK_UNCORRECTABLE
+                | CUresult::CUDA_ERROR_ILLEGAL_ADDRESS
+                | CUresult::CUDA_ERROR_LAUNCH_TIMEOUT
+                | CUresult::CUDA_ERROR_ASSERT
+                | CUresult::CUDA_ERROR_HARDWARE_STACK_ERROR
+                | CUresult::CUDA_ERROR_ILLEGAL_INSTRUCTION
+                | CUresult::CUDA_ERROR_MISALIGNED_ADDRESS
+                | CUresult::CUDA_ERROR_INVALID_ADDRESS_SPACE
+                | CUresult::CUDA_ERROR_INVALID_PC
+                | CUresult::CUDA_ERROR_LAUNCH_FAILED
+                | CUresult::CUDA_ERROR_EXTERNAL_DEVICE
+        )
+    }
+}
+
+/// A result code as returned by the driver.
+///
+/// Newer drivers may return codes that are not (yet) part of [`CUresult`], so entry points return
+/// the raw code, which is converted once it reaches safe code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct CUresultCode(pub c_int);
+
+impl CUresultCode {
+    pub fn is_success(self) -> bool {
+        self == CUresult::CUDA_SUCCESS.into()
+    }
+
+    /// Converts the code into a [`CUresult`], or returns it if it is unknown.
+    pub fn get(self) -> Result<CUresult, c_int> {
+        CUresult::try_from(self.0).map_err(|err| err.number)
+    }
+}
+
+impl From<CUresult> for CUresultCode {
+    fn from(value: CUresult) -> Self {
+        Self(value as c_int)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+#[repr(i32)]
 pub enum CUresult {
     CUDA_SUCCESS = 0,
     CUDA_ERROR_INVALID_VALUE = 1,
@@ -146,32 +204,267 @@ pub enum CUresult {
     CUDA_ERROR_INVALID_CLUSTER_SIZE = 912,
     CUDA_ERROR_UNKNOWN = 999,
 }
-impl CUresult {
+/// The result of a driver call made through [`Api`].
+#[must_use]
+pub struct ApiResult<'a> {
+    driver: &'a dyn Driver,
+    function: &'static str,
+    result: CUresultCode,
+}
+
+impl ApiResult<'_> {
+    /// The result of the call, or the raw code if it is unknown to [`CUresult`].
+    pub fn result(&self) -> Result<CUresult, c_int> {
+        self.result.get()
+    }
+
+    /// Turns a failed call into a [`CUError::CUResult`] carrying the driver's name and
+    /// description of the error as well as the call site.
+    /// Codes unknown to [`CUresult`] become [`CUError::CUUnknownResult`].
+    #[track_caller]
     pub fn check(self) -> crate::cuda_result::Result<()> {
-        if self != CUresult::CUDA_SUCCESS {
-            error!("CUDA Error {:?} with code: {}", self, self as i32);
+        let result = match self.result.get() {
+            Ok(CUresult::CUDA_SUCCESS) => return Ok(()),
+            Ok(result) => result,
+            Err(code) => {
+                let error = UnknownResultError {
+                    function: self.function,
+                    code,
+                    name: self.error_str(false),
+                    location: Location::caller(),
+                };
+                error!("{error}");
+                return Err(CUError::CUUnknownResult(error));
+            }
+        };
+        let error = ApiError {
+            function: self.function,
+            result,
+            name: self.error_str(false),
+            description: self.error_str(true),
+            location: Location::caller(),
+        };
+        error!("{error}");
+        Err(CUError::CUResult(error))
+    }
+
+    /// Looks up the name or the description of the result.
+    fn error_str(&self, description: bool) -> Option<String> {
+        let mut ptr: *const c_char = null();
+        unsafe {
+            let result = if description {
+                self.driver.cuGetErrorString(self.result, &mut ptr)
+            } else {
+                self.driver.cuGetErrorName(self.result, &mut ptr)
+            };
+            if !result.is_success() || ptr.is_null() {
+                return None;
+            }
+            Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
         }
-        self.into()
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(C)]
+/// `CUdevice_attribute`, the attributes queried by `cuDeviceGetAttribute` (as of CUDA 12.4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[repr(i32)]
 pub enum CUAttribute {
-    CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MAJOR = 75,
-    CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MINOR = 76,
-    CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN = 97,
-    CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED = 115,
+    CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_BLOCK = 1,
+    CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_X = 2,
+    CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Y = 3,
+    CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Z = 4,
+    CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_X = 5,
+    CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Y = 6,
+    CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Z = 7,
+    CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK = 8,
+    CU_DEVICE_ATTRIBUTE_TOTAL_CONSTANT_MEMORY = 9,
+    CU_DEVICE_ATTRIBUTE_WARP_SIZE = 10,
+    CU_DEVICE_ATTRIBUTE_MAX_PITCH = 11,
+    CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_BLOCK = 12,
+    CU_DEVICE_ATTRIBUTE_CLOCK_RATE = 13,
+    CU_DEVICE_ATTRIBUTE_TEXTURE_ALIGNMENT = 14,
+    CU_DEVICE_ATTRIBUTE_GPU_OVERLAP = 15,
     CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT = 16,
+    CU_DEVICE_ATTRIBUTE_KERNEL_EXEC_TIMEOUT = 17,
+    CU_DEVICE_ATTRIBUTE_INTEGRATED = 18,
+    CU_DEVICE_ATTRIBUTE_CAN_MAP_HOST_MEMORY = 19,
+    CU_DEVICE_ATTRIBUTE_COMPUTE_MODE = 20,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_WIDTH = 21,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_WIDTH = 22,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_HEIGHT = 23,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_WIDTH = 24,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_HEIGHT = 25,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_DEPTH = 26,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LAYERED_WIDTH = 27,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LAYERED_HEIGHT = 28,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LAYERED_LAYERS = 29,
+    CU_DEVICE_ATTRIBUTE_SURFACE_ALIGNMENT = 30,
+    CU_DEVICE_ATTRIBUTE_CONCURRENT_KERNELS = 31,
+    CU_DEVICE_ATTRIBUTE_ECC_ENABLED = 32,
     CU_DEVICE_ATTRIBUTE_PCI_BUS_ID = 33,
     CU_DEVICE_ATTRIBUTE_PCI_DEVICE_ID = 34,
-    CU_DEVICE_ATTRIBUTE_PCI_DOMAIN_ID = 50,
-    CU_DEVICE_ATTRIBUTE_UNIFIED_ADDRESSING = 41,
     CU_DEVICE_ATTRIBUTE_TCC_DRIVER = 35,
+    CU_DEVICE_ATTRIBUTE_MEMORY_CLOCK_RATE = 36,
+    CU_DEVICE_ATTRIBUTE_GLOBAL_MEMORY_BUS_WIDTH = 37,
+    CU_DEVICE_ATTRIBUTE_L2_CACHE_SIZE = 38,
+    CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_MULTIPROCESSOR = 39,
+    CU_DEVICE_ATTRIBUTE_ASYNC_ENGINE_COUNT = 40,
+    CU_DEVICE_ATTRIBUTE_UNIFIED_ADDRESSING = 41,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_LAYERED_WIDTH = 42,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_LAYERED_LAYERS = 43,
+    CU_DEVICE_ATTRIBUTE_CAN_TEX2D_GATHER = 44,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_GATHER_WIDTH = 45,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_GATHER_HEIGHT = 46,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_WIDTH_ALTERNATE = 47,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_HEIGHT_ALTERNATE = 48,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_DEPTH_ALTERNATE = 49,
+    CU_DEVICE_ATTRIBUTE_PCI_DOMAIN_ID = 50,
+    CU_DEVICE_ATTRIBUTE_TEXTURE_PITCH_ALIGNMENT = 51,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURECUBEMAP_WIDTH = 52,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURECUBEMAP_LAYERED_WIDTH = 53,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURECUBEMAP_LAYERED_LAYERS = 54,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE1D_WIDTH = 55,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE2D_WIDTH = 56,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE2D_HEIGHT = 57,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE3D_WIDTH = 58,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE3D_HEIGHT = 59,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE3D_DEPTH = 60,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE1D_LAYERED_WIDTH = 61,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE1D_LAYERED_LAYERS = 62,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE2D_LAYERED_WIDTH = 63,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE2D_LAYERED_HEIGHT = 64,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACE2D_LAYERED_LAYERS = 65,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACECUBEMAP_WIDTH = 66,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACECUBEMAP_LAYERED_WIDTH = 67,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_SURFACECUBEMAP_LAYERED_LAYERS = 68,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_LINEAR_WIDTH = 69,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LINEAR_WIDTH = 70,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LINEAR_HEIGHT = 71,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LINEAR_PITCH = 72,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_MIPMAPPED_WIDTH = 73,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_MIPMAPPED_HEIGHT = 74,
+    CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MAJOR = 75,
+    CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MINOR = 76,
+    CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_MIPMAPPED_WIDTH = 77,
+    CU_DEVICE_ATTRIBUTE_STREAM_PRIORITIES_SUPPORTED = 78,
+    CU_DEVICE_ATTRIBUTE_GLOBAL_L1_CACHE_SUPPORTED = 79,
+    CU_DEVICE_ATTRIBUTE_LOCAL_L1_CACHE_SUPPORTED = 80,
+    CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_MULTIPROCESSOR = 81,
+    CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_MULTIPROCESSOR = 82,
+    CU_DEVICE_ATTRIBUTE_MANAGED_MEMORY = 83,
+    CU_DEVICE_ATTRIBUTE_MULTI_GPU_BOARD = 84,
+    CU_DEVICE_ATTRIBUTE_MULTI_GPU_BOARD_GROUP_ID = 85,
+    CU_DEVICE_ATTRIBUTE_HOST_NATIVE_ATOMIC_SUPPORTED = 86,
+    CU_DEVICE_ATTRIBUTE_SINGLE_TO_DOUBLE_PRECISION_PERF_RATIO = 87,
+    CU_DEVICE_ATTRIBUTE_PAGEABLE_MEMORY_ACCESS = 88,
+    CU_DEVICE_ATTRIBUTE_CONCURRENT_MANAGED_ACCESS = 89,
     CU_DEVICE_ATTRIBUTE_COMPUTE_PREEMPTION_SUPPORTED = 90,
+    CU_DEVICE_ATTRIBUTE_CAN_USE_HOST_POINTER_FOR_REGISTERED_MEM = 91,
+    CU_DEVICE_ATTRIBUTE_CAN_USE_STREAM_MEM_OPS_V1 = 92,
+    CU_DEVICE_ATTRIBUTE_CAN_USE_64_BIT_STREAM_MEM_OPS_V1 = 93,
+    CU_DEVICE_ATTRIBUTE_CAN_USE_STREAM_WAIT_VALUE_NOR_V1 = 94,
+    CU_DEVICE_ATTRIBUTE_COOPERATIVE_LAUNCH = 95,
+    CU_DEVICE_ATTRIBUTE_COOPERATIVE_MULTI_DEVICE_LAUNCH = 96,
+    CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN = 97,
+    CU_DEVICE_ATTRIBUTE_CAN_FLUSH_REMOTE_WRITES = 98,
+    CU_DEVICE_ATTRIBUTE_HOST_REGISTER_SUPPORTED = 99,
+    CU_DEVICE_ATTRIBUTE_PAGEABLE_MEMORY_ACCESS_USES_HOST_PAGE_TABLES = 100,
+    CU_DEVICE_ATTRIBUTE_DIRECT_MANAGED_MEM_ACCESS_FROM_HOST = 101,
+    CU_DEVICE_ATTRIBUTE_VIRTUAL_MEMORY_MANAGEMENT_SUPPORTED = 102,
+    CU_DEVICE_ATTRIBUTE_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR_SUPPORTED = 103,
+    CU_DEVICE_ATTRIBUTE_HANDLE_TYPE_WIN32_HANDLE_SUPPORTED = 104,
+    CU_DEVICE_ATTRIBUTE_HANDLE_TYPE_WIN32_KMT_HANDLE_SUPPORTED = 105,
+    CU_DEVICE_ATTRIBUTE_MAX_BLOCKS_PER_MULTIPROCESSOR = 106,
+    CU_DEVICE_ATTRIBUTE_GENERIC_COMPRESSION_SUPPORTED = 107,
+    CU_DEVICE_ATTRIBUTE_MAX_PERSISTING_L2_CACHE_SIZE = 108,
+    CU_DEVICE_ATTRIBUTE_MAX_ACCESS_POLICY_WINDOW_SIZE = 109,
+    CU_DEVICE_ATTRIBUTE_GPU_DIRECT_RDMA_WITH_CUDA_VMM_SUPPORTED = 110,
+    CU_DEVICE_ATTRIBUTE_RESERVED_SHARED_MEMORY_PER_BLOCK = 111,
+    CU_DEVICE_ATTRIBUTE_SPARSE_CUDA_ARRAY_SUPPORTED = 112,
+    CU_DEVICE_ATTRIBUTE_READ_ONLY_HOST_REGISTER_SUPPORTED = 113,
+    CU_DEVICE_ATTRIBUTE_TIMELINE_SEMAPHORE_INTEROP_SUPPORTED = 114,
+    CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED = 115,
+    CU_DEVICE_ATTRIBUTE_GPU_DIRECT_RDMA_SUPPORTED = 116,
+    CU_DEVICE_ATTRIBUTE_GPU_DIRECT_RDMA_FLUSH_WRITES_OPTIONS = 117,
+    CU_DEVICE_ATTRIBUTE_GPU_DIRECT_RDMA_WRITES_ORDERING = 118,
+    CU_DEVICE_ATTRIBUTE_MEMPOOL_SUPPORTED_HANDLE_TYPES = 119,
+    CU_DEVICE_ATTRIBUTE_CLUSTER_LAUNCH = 120,
+    CU_DEVICE_ATTRIBUTE_DEFERRED_MAPPING_CUDA_ARRAY_SUPPORTED = 121,
+    CU_DEVICE_ATTRIBUTE_CAN_USE_64_BIT_STREAM_MEM_OPS = 122,
+    CU_DEVICE_ATTRIBUTE_CAN_USE_STREAM_WAIT_VALUE_NOR = 123,
+    CU_DEVICE_ATTRIBUTE_DMA_BUF_SUPPORTED = 124,
+    CU_DEVICE_ATTRIBUTE_IPC_EVENT_SUPPORTED = 125,
+    CU_DEVICE_ATTRIBUTE_MEM_SYNC_DOMAIN_COUNT = 126,
+    CU_DEVICE_ATTRIBUTE_TENSOR_MAP_ACCESS_SUPPORTED = 127,
+    CU_DEVICE_ATTRIBUTE_HANDLE_TYPE_FABRIC_SUPPORTED = 128,
+    CU_DEVICE_ATTRIBUTE_UNIFIED_FUNCTION_POINTERS = 129,
+    CU_DEVICE_ATTRIBUTE_NUMA_CONFIG = 130,
+    CU_DEVICE_ATTRIBUTE_NUMA_ID = 131,
+    CU_DEVICE_ATTRIBUTE_MULTICAST_SUPPORTED = 132,
+    CU_DEVICE_ATTRIBUTE_MPS_ENABLED = 133,
+    CU_DEVICE_ATTRIBUTE_HOST_NUMA_ID = 134,
 }
 
-type size_t = c_ulong;
+/// `CUlimit`, the limits of a context set by `cuCtxSetLimit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[repr(i32)]
+pub enum CUlimit {
+    /// Stack size in bytes of each GPU thread.
+    CU_LIMIT_STACK_SIZE = 0x00,
+    /// Size in bytes of the FIFO used by `printf` in kernels.
+    CU_LIMIT_PRINTF_FIFO_SIZE = 0x01,
+    /// Size in bytes of the heap used by `malloc` in kernels.
+    CU_LIMIT_MALLOC_HEAP_SIZE = 0x02,
+    /// Maximum nesting depth of a grid at which a thread can synchronize with its children.
+    CU_LIMIT_DEV_RUNTIME_SYNC_DEPTH = 0x03,
+    /// Maximum number of outstanding device runtime launches.
+    CU_LIMIT_DEV_RUNTIME_PENDING_LAUNCH_COUNT = 0x04,
+    /// L2 cache fetch granularity in bytes, between 0 and 128.
+    CU_LIMIT_MAX_L2_FETCH_GRANULARITY = 0x05,
+    /// Size in bytes of the persisting L2 cache.
+    CU_LIMIT_PERSISTING_L2_CACHE_SIZE = 0x06,
+}
+
+/// `CUfunction_attribute`, the attributes of a kernel function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[repr(i32)]
+pub enum CUFuncAttribute {
+    CU_FUNC_ATTRIBUTE_MAX_THREADS_PER_BLOCK = 0,
+    CU_FUNC_ATTRIBUTE_SHARED_SIZE_BYTES = 1,
+    CU_FUNC_ATTRIBUTE_CONST_SIZE_BYTES = 2,
+    CU_FUNC_ATTRIBUTE_LOCAL_SIZE_BYTES = 3,
+    CU_FUNC_ATTRIBUTE_NUM_REGS = 4,
+    CU_FUNC_ATTRIBUTE_PTX_VERSION = 5,
+    CU_FUNC_ATTRIBUTE_BINARY_VERSION = 6,
+    CU_FUNC_ATTRIBUTE_CACHE_MODE_CA = 7,
+    CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES = 8,
+    CU_FUNC_ATTRIBUTE_PREFERRED_SHARED_MEMORY_CARVEOUT = 9,
+    CU_FUNC_ATTRIBUTE_CLUSTER_SIZE_MUST_BE_SET = 10,
+    CU_FUNC_ATTRIBUTE_REQUIRED_CLUSTER_WIDTH = 11,
+    CU_FUNC_ATTRIBUTE_REQUIRED_CLUSTER_HEIGHT = 12,
+    CU_FUNC_ATTRIBUTE_REQUIRED_CLUSTER_DEPTH = 13,
+    CU_FUNC_ATTRIBUTE_NON_PORTABLE_CLUSTER_SIZE_ALLOWED = 14,
+    CU_FUNC_ATTRIBUTE_CLUSTER_SCHEDULING_POLICY_PREFERENCE = 15,
+}
+
+/// `CUfunc_cache`, the preferred split between L1 cache and shared memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[repr(i32)]
+pub enum CUFuncCache {
+    #[default]
+    CU_FUNC_CACHE_PREFER_NONE = 0,
+    CU_FUNC_CACHE_PREFER_SHARED = 1,
+    CU_FUNC_CACHE_PREFER_L1 = 2,
+    CU_FUNC_CACHE_PREFER_EQUAL = 3,
+}
+
+pub type size_t = c_ulong;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct CUuuid {
+    pub bytes: [c_uchar; 16],
+}
 
 #[derive(Default, Clone, Copy)]
 #[repr(C)]
@@ -253,15 +546,15 @@ pub struct CUDA_MEMCPY2D {
     pub srcY: size_t,
     pub srcMemoryType: c_int,
     pub srcHost: *const c_void,
-    pub srcDevice: c_int,
-    pub srcArray: c_int,
+    pub srcDevice: CUdeviceptr,
+    pub srcArray: CUarray,
     pub srcPitch: size_t,
     pub dstXInBytes: size_t,
     pub dstY: size_t,
     pub dstMemoryType: c_int,
     pub dstHost: *mut c_void,
-    pub dstDevice: c_int,
-    pub dstArray: c_int,
+    pub dstDevice: CUdeviceptr,
+    pub dstArray: CUarray,
     pub dstPitch: size_t,
     pub WidthInBytes: size_t,
     pub Height: size_t,
@@ -276,8 +569,8 @@ pub struct CUDA_MEMCPY3D {
     pub srcLOD: size_t,
     pub srcMemoryType: c_int,
     pub srcHost: *const c_void,
-    pub srcDevice: c_int,
-    pub srcArray: c_int,
+    pub srcDevice: CUdeviceptr,
+    pub srcArray: CUarray,
     pub reserved0: *mut c_void,
     pub srcPitch: size_t,
     pub srcHeight: size_t,
@@ -287,8 +580,8 @@ pub struct CUDA_MEMCPY3D {
     pub dstLOD: size_t,
     pub dstMemoryType: c_int,
     pub dstHost: *mut c_void,
-    pub dstDevice: c_int,
-    pub dstArray: c_int,
+    pub dstDevice: CUdeviceptr,
+    pub dstArray: CUarray,
     pub reserved1: *mut c_void,
     pub dstPitch: size_t,
     pub dstHeight: size_t,
@@ -299,9 +592,12 @@ pub struct CUDA_MEMCPY3D {
 
 pub const CU_DEVICE_CPU: c_int = -1;
 
-pub const CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES: c_int = 8;
-pub const CU_FUNC_ATTRIBUTE_PREFERRED_SHARED_MEMORY_CARVEOUT: c_int = 9;
-pub const CU_FUNC_CACHE_PREFER_L1: c_int = 2;
+pub const CU_CTX_SCHED_AUTO: c_uint = 0x00;
+pub const CU_CTX_SCHED_SPIN: c_uint = 0x01;
+pub const CU_CTX_SCHED_YIELD: c_uint = 0x02;
+pub const CU_CTX_SCHED_BLOCKING_SYNC: c_uint = 0x04;
+pub const CU_CTX_SCHED_MASK: c_uint = 0x07;
+pub const CU_CTX_LMEM_RESIZE_TO_MAX: c_uint = 0x10;
 
 pub const CU_JIT_INPUT_PTX: c_int = 1;
 pub const CU_JIT_INFO_LOG_BUFFER: c_int = 3;
@@ -343,43 +639,273 @@ pub const CU_RES_VIEW_FORMAT_FLOAT_1X32: c_int = 0x16;
 pub const CU_RES_VIEW_FORMAT_FLOAT_2X32: c_int = 0x17;
 pub const CU_RES_VIEW_FORMAT_FLOAT_4X32: c_int = 0x18;
 
-#[derive(WrapperApi)]
-pub struct CudaApi {
-    cuCtxEnablePeerAccess: unsafe extern "C" fn(peerContext: CUcontext, Flags: c_uint) -> CUresult,
-    cuCtxSynchronize: unsafe extern "C" fn() -> CUresult,
+/// Version of the driver API whose signatures are declared in this module.
+/// Versioned entry points are requested for this version (or the driver's, if older).
+pub const API_VERSION: c_int = 12000;
+
+pub const CU_GET_PROC_ADDRESS_DEFAULT: u64 = 0;
+
+// Resolved by the loader before the other entry points.
+pub type PFN_cuGetProcAddress = unsafe extern "C" fn(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cudaVersion: c_int,
+    flags: u64,
+) -> CUresultCode;
+pub type PFN_cuDriverGetVersion = unsafe extern "C" fn(driverVersion: *mut c_int) -> CUresultCode;
+
+/// Declares the driver entry points.
+///
+/// Generates the dynamically loaded [`CudaApi`] table, the [`Driver`] backend trait and the
+/// implementation of that trait for the loaded library.
+/// Entry points marked with `#[since(version)]` are optional and only resolved if the driver
+/// exports them, so that older drivers can still be loaded.
+/// Entry points marked with `#[symbol(name)]` have been revised and are exported under a
+/// versioned name, the unversioned symbol is a legacy shim with a different ABI.
+/// `#[symbol(name, legacy)]` names a symbol with the same ABI that drivers export instead
+/// before they introduced the versioned one.
+macro_rules! driver_api {
+    ($($(#[since($since:literal)])? $(#[symbol($symbol:literal $(, $legacy:literal)?)])? $name:ident: unsafe extern "C" fn($($arg:ident: $ty:ty),* $(,)?) -> CUresultCode,)*) => {
+        pub struct CudaApi {
+            $($name: Option<unsafe extern "C" fn($($arg: $ty),*) -> CUresultCode>,)*
+            resolutions: Vec<(&'static str, Resolution)>,
+            // Keeps the symbols above alive.
+            source: Box<dyn SymbolSource>,
+        }
+
+        impl CudaApi {
+            /// Opens the driver library at `path` and resolves its entry points.
+            pub unsafe fn load(path: impl AsRef<OsStr>) -> Result<Self, dlopen::Error> {
+                Self::load_from(Box::new(Library::open(path)?))
+            }
+
+            /// Resolves the entry points from `source`.
+            pub unsafe fn load_from(source: Box<dyn SymbolSource>) -> Result<Self, dlopen::Error> {
+                let resolver = Resolver::new(source.as_ref());
+                let mut resolutions = vec![];
+                Ok(Self {
+                    $($name: driver_api!(
+                        @load resolver,
+                        resolutions,
+                        $name,
+                        driver_api!(@symbol $name $(, $symbol)?),
+                        driver_api!(@legacy $($($legacy)?)?),
+                        unsafe extern "C" fn($($arg: $ty),*) -> CUresultCode
+                        $(, $since)?
+                    ),)*
+                    resolutions,
+                    source,
+                })
+            }
+
+            /// Driver version that introduced the optional entry point `name`.
+            /// Returns `None` for required and unknown entry points.
+            pub fn since(name: &str) -> Option<c_int> {
+                match name {
+                    $(stringify!($name) => driver_api!(@since $($since)?),)*
+                    _ => None,
+                }
+            }
+
+            /// How the entry point `name` was resolved, or `None` if the driver lacks it.
+            pub fn resolution(&self, name: &str) -> Option<&Resolution> {
+                self.resolutions
+                    .iter()
+                    .find(|(entry, _)| *entry == name)
+                    .map(|(_, resolution)| resolution)
+            }
+        }
+
+        /// Checked access to a [`Driver`].
+        ///
+        /// Mirrors the entry points of the driver, but returns an [`ApiResult`] that remembers
+        /// which entry point failed.
+        /// Calls are recorded in a [`Trace`] if one is set, which can be changed at any time.
+        pub struct Api {
+            driver: Box<dyn Driver>,
+            trace: DebugMutex<Option<Arc<Trace>>>,
+        }
+
+        impl Api {
+            pub fn new(driver: Box<dyn Driver>) -> Self {
+                Self {
+                    driver,
+                    trace: DebugMutex::new(None),
+                }
+            }
+
+            pub fn driver(&self) -> &dyn Driver {
+                self.driver.as_ref()
+            }
+
+            pub fn trace(&self) -> Option<Arc<Trace>> {
+                self.trace.lock().clone()
+            }
+
+            /// Records every following call in `trace`, or stops recording if it is `None`.
+            /// Returns the previous recording.
+            pub fn set_trace(&self, trace: Option<Arc<Trace>>) -> Option<Arc<Trace>> {
+                std::mem::replace(&mut *self.trace.lock(), trace)
+            }
+
+            /// Returns the current recording, or starts recording in the one `create` returns.
+            pub fn trace_or_insert_with(&self, create: impl FnOnce() -> Arc<Trace>) -> Arc<Trace> {
+                self.trace.lock().get_or_insert_with(create).clone()
+            }
+            $(
+                pub unsafe fn $name(&self, $($arg: $ty),*) -> ApiResult<'_> {
+                    // The lock is not held during the call, which may take long.
+                    let result = match self.trace() {
+                        Some(trace) => {
+                            let args = [$((stringify!($arg), format!("{:?}", $arg))),*];
+                            let start = Instant::now();
+                            let result = self.driver.$name($($arg),*);
+                            trace.record(stringify!($name), &args, result, start);
+                            result
+                        }
+                        None => self.driver.$name($($arg),*),
+                    };
+                    ApiResult {
+                        driver: self.driver.as_ref(),
+                        function: stringify!($name),
+                        result,
+                    }
+                }
+            )*
+        }
+
+        /// Backend that executes driver calls.
+        ///
+        /// The safe layer only talks to the driver through this trait, which is implemented by the
+        /// dynamically loaded driver library and by [`crate::fake::FakeDriver`].
+        /// Entry points a backend does not implement report `CUDA_ERROR_NOT_SUPPORTED`.
+        pub trait Driver: Send + Sync {
+            /// Called by every entry point the backend does not implement.
+            fn unsupported(&self, name: &'static str) -> CUresultCode {
+                CUresult::CUDA_ERROR_NOT_SUPPORTED.into()
+            }
+            /// Whether the entry point `name` can be called.
+            fn has_entry_point(&self, name: &str) -> bool {
+                true
+            }
+            $(
+                unsafe fn $name(&self, $($arg: $ty),*) -> CUresultCode {
+                    self.unsupported(stringify!($name))
+                }
+            )*
+        }
+
+        impl Driver for CudaApi {
+            fn has_entry_point(&self, name: &str) -> bool {
+                match name {
+                    $(stringify!($name) => self.$name.is_some(),)*
+                    _ => false,
+                }
+            }
+            $(
+                unsafe fn $name(&self, $($arg: $ty),*) -> CUresultCode {
+                    match self.$name {
+                        Some(f) => f($($arg),*),
+                        None => self.unsupported(stringify!($name)),
+                    }
+                }
+            )*
+        }
+    };
+    (@load $resolver:ident, $resolutions:ident, $name:ident, $symbol:expr, $legacy:expr, $fn:ty $(, $since:literal)?) => {
+        match $resolver.resolve(stringify!($name), $symbol, $legacy) {
+            Some((ptr, resolution)) => {
+                $resolutions.push((stringify!($name), resolution));
+                Some(std::mem::transmute::<*const c_void, $fn>(ptr))
+            }
+            None => driver_api!(@missing $symbol $(, $since)?),
+        }
+    };
+    (@missing $symbol:expr) => {
+        return Err(dlopen::Error::SymbolGettingError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} not found", $symbol),
+        )))
+    };
+    (@missing $symbol:expr, $since:literal) => {
+        None
+    };
+    (@symbol $name:ident) => {
+        stringify!($name)
+    };
+    (@symbol $name:ident, $symbol:literal) => {
+        $symbol
+    };
+    (@legacy) => {
+        None
+    };
+    (@legacy $legacy:literal) => {
+        Some($legacy)
+    };
+    (@since) => {
+        None
+    };
+    (@since $since:literal) => {
+        Some($since)
+    };
+}
+
+driver_api! {
+    cuCtxEnablePeerAccess: unsafe extern "C" fn(peerContext: CUcontext, Flags: c_uint) -> CUresultCode,
+    cuCtxGetLimit: unsafe extern "C" fn(pvalue: *mut size_t, limit: c_int) -> CUresultCode,
+    cuCtxGetStreamPriorityRange: unsafe extern "C" fn(
+        leastPriority: *mut c_int,
+        greatestPriority: *mut c_int,
+    ) -> CUresultCode,
+    cuCtxSetLimit: unsafe extern "C" fn(limit: c_int, value: size_t) -> CUresultCode,
+    cuCtxSynchronize: unsafe extern "C" fn() -> CUresultCode,
     cuDeviceCanAccessPeer: unsafe extern "C" fn(
         canAccessPeer: *mut c_int,
         dev: CUdevice,
         peerDev: CUdevice,
-    ) -> CUresult,
-    cuDeviceGet: unsafe extern "C" fn(device: *mut CUdevice, ordinal: c_int) -> CUresult,
+    ) -> CUresultCode,
+    cuDeviceGet: unsafe extern "C" fn(device: *mut CUdevice, ordinal: c_int) -> CUresultCode,
     cuDeviceGetAttribute:
-        unsafe extern "C" fn(pi: *mut c_int, attrib: c_int, dev: CUdevice) -> CUresult,
-    cuDeviceGetCount: unsafe extern "C" fn(count: *mut c_int) -> CUresult,
-    cuDeviceGetName: unsafe extern "C" fn(name: *mut c_char, len: c_int, dev: CUdevice) -> CUresult,
-    cuDevicePrimaryCtxRelease: unsafe extern "C" fn(dev: CUdevice) -> CUresult,
-    cuDevicePrimaryCtxRetain: unsafe extern "C" fn(pctx: *mut CUcontext, dev: CUdevice) -> CUresult,
-    cuDeviceTotalMem: unsafe extern "C" fn(bytes: *mut size_t, dev: CUdevice) -> CUresult,
-    cuDriverGetVersion: unsafe extern "C" fn(driverVersion: *mut c_int) -> CUresult,
-    cuEventCreate: unsafe extern "C" fn(phEvent: *mut CUevent, Flags: c_uint) -> CUresult,
-    cuEventDestroy: unsafe extern "C" fn(hEvent: CUevent) -> CUresult,
-    cuEventRecord: unsafe extern "C" fn(hEvent: CUevent, hStream: CUstream) -> CUresult,
-    cuEventSynchronize: unsafe extern "C" fn(hEvent: CUevent) -> CUresult,
+        unsafe extern "C" fn(pi: *mut c_int, attrib: c_int, dev: CUdevice) -> CUresultCode,
+    cuDeviceGetCount: unsafe extern "C" fn(count: *mut c_int) -> CUresultCode,
+    cuDeviceGetName: unsafe extern "C" fn(name: *mut c_char, len: c_int, dev: CUdevice) -> CUresultCode,
+    cuDeviceGetUuid: unsafe extern "C" fn(uuid: *mut CUuuid, dev: CUdevice) -> CUresultCode,
+    cuDevicePrimaryCtxGetState:
+        unsafe extern "C" fn(dev: CUdevice, flags: *mut c_uint, active: *mut c_int) -> CUresultCode,
+    #[symbol("cuDevicePrimaryCtxRelease_v2", "cuDevicePrimaryCtxRelease")]
+    cuDevicePrimaryCtxRelease: unsafe extern "C" fn(dev: CUdevice) -> CUresultCode,
+    #[symbol("cuDevicePrimaryCtxReset_v2", "cuDevicePrimaryCtxReset")]
+    cuDevicePrimaryCtxReset: unsafe extern "C" fn(dev: CUdevice) -> CUresultCode,
+    cuDevicePrimaryCtxRetain: unsafe extern "C" fn(pctx: *mut CUcontext, dev: CUdevice) -> CUresultCode,
+    #[symbol("cuDevicePrimaryCtxSetFlags_v2", "cuDevicePrimaryCtxSetFlags")]
+    cuDevicePrimaryCtxSetFlags: unsafe extern "C" fn(dev: CUdevice, flags: c_uint) -> CUresultCode,
+    #[symbol("cuDeviceTotalMem_v2")]
+    cuDeviceTotalMem: unsafe extern "C" fn(bytes: *mut size_t, dev: CUdevice) -> CUresultCode,
+    cuDriverGetVersion: unsafe extern "C" fn(driverVersion: *mut c_int) -> CUresultCode,
+    cuEventCreate: unsafe extern "C" fn(phEvent: *mut CUevent, Flags: c_uint) -> CUresultCode,
+    #[symbol("cuEventDestroy_v2")]
+    cuEventDestroy: unsafe extern "C" fn(hEvent: CUevent) -> CUresultCode,
+    cuEventQuery: unsafe extern "C" fn(hEvent: CUevent) -> CUresultCode,
+    cuEventRecord: unsafe extern "C" fn(hEvent: CUevent, hStream: CUstream) -> CUresultCode,
+    cuEventSynchronize: unsafe extern "C" fn(hEvent: CUevent) -> CUresultCode,
     cuEventElapsedTime: unsafe extern "C" fn(
         pMilliseconds: *mut c_float,
         hStart: CUevent,
         hEnd: CUevent,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    cuFuncGetAttribute:
+        unsafe extern "C" fn(pi: *mut c_int, attrib: c_int, hfunc: CUfunction) -> CUresultCode,
     cuFuncSetAttribute:
-        unsafe extern "C" fn(hfunc: CUfunction, attrib: c_int, value: c_int) -> CUresult,
-    cuGetErrorName: unsafe extern "C" fn(error: CUresult, pStr: *const *mut c_char) -> CUresult,
-    cuGetErrorString: unsafe extern "C" fn(error: CUresult, pStr: *const *mut c_char) -> CUresult,
-    cuInit: unsafe extern "C" fn(Flags: c_uint) -> CUresult,
+        unsafe extern "C" fn(hfunc: CUfunction, attrib: c_int, value: c_int) -> CUresultCode,
+    cuFuncSetCacheConfig: unsafe extern "C" fn(hfunc: CUfunction, config: c_int) -> CUresultCode,
+    cuGetErrorName: unsafe extern "C" fn(error: CUresultCode, pStr: *mut *const c_char) -> CUresultCode,
+    cuGetErrorString: unsafe extern "C" fn(error: CUresultCode, pStr: *mut *const c_char) -> CUresultCode,
+    cuInit: unsafe extern "C" fn(Flags: c_uint) -> CUresultCode,
     cuLaunchHostFunc: unsafe extern "C" fn(
         hStream: CUstream,
         func: unsafe extern "C" fn(*mut c_void),
         userData: *mut c_void,
-    ) -> CUresult,
+    ) -> CUresultCode,
     cuLaunchKernel: unsafe extern "C" fn(
         f: CUfunction,
         gridDimX: c_uint,
@@ -392,7 +918,8 @@ pub struct CudaApi {
         hStream: CUstream,
         kernelParams: *mut *mut c_void,
         extra: *mut *mut c_void,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuLinkAddData_v2")]
     cuLinkAddData: unsafe extern "C" fn(
         state: CUlinkState,
         ty: c_int,
@@ -402,64 +929,92 @@ pub struct CudaApi {
         numOptions: c_uint,
         options: *mut c_int,
         optionValues: *mut *mut c_void,
-    ) -> CUresult,
+    ) -> CUresultCode,
     cuLinkComplete: unsafe extern "C" fn(
         state: CUlinkState,
         cubinOut: *mut *mut c_void,
         sizeOut: *mut size_t,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuLinkCreate_v2")]
     cuLinkCreate: unsafe extern "C" fn(
         numOptions: c_uint,
         options: *mut c_int,
         optionValues: *mut *mut c_void,
         stateOut: *mut CUlinkState,
-    ) -> CUresult,
-    cuLinkDestroy: unsafe extern "C" fn(state: CUlinkState) -> CUresult,
+    ) -> CUresultCode,
+    cuLinkDestroy: unsafe extern "C" fn(state: CUlinkState) -> CUresultCode,
     cuPointerGetAttribute:
-        unsafe extern "C" fn(data: *mut c_void, attribute: c_int, ptr: *mut c_void) -> CUresult,
+        unsafe extern "C" fn(data: *mut c_void, attribute: c_int, ptr: *mut c_void) -> CUresultCode,
     cuMemAdvise: unsafe extern "C" fn(
         devPtr: *mut c_void,
         count: size_t,
         advice: c_int,
         device: CUdevice,
-    ) -> CUresult,
-    cuMemAlloc: unsafe extern "C" fn(dptr: *mut *mut c_void, bytesize: size_t) -> CUresult,
-    cuMemAllocHost: unsafe extern "C" fn(pp: *mut *mut c_void, bytesize: size_t) -> CUresult,
-    cuMemFree: unsafe extern "C" fn(dptr: *mut c_void) -> CUresult,
-    cuMemFreeHost: unsafe extern "C" fn(p: *mut c_void) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuMemAlloc_v2")]
+    cuMemAlloc: unsafe extern "C" fn(dptr: *mut *mut c_void, bytesize: size_t) -> CUresultCode,
+    #[symbol("cuMemAllocHost_v2")]
+    cuMemAllocHost: unsafe extern "C" fn(pp: *mut *mut c_void, bytesize: size_t) -> CUresultCode,
+    #[symbol("cuMemFree_v2")]
+    cuMemFree: unsafe extern "C" fn(dptr: *mut c_void) -> CUresultCode,
+    cuMemFreeHost: unsafe extern "C" fn(p: *mut c_void) -> CUresultCode,
+    #[symbol("cuMemGetInfo_v2")]
+    cuMemGetInfo: unsafe extern "C" fn(free: *mut size_t, total: *mut size_t) -> CUresultCode,
     cuMemcpy:
-        unsafe extern "C" fn(dst: *mut c_void, src: *const c_void, ByteCount: size_t) -> CUresult,
+        unsafe extern "C" fn(dst: *mut c_void, src: *const c_void, ByteCount: size_t) -> CUresultCode,
     cuMemcpyAsync: unsafe extern "C" fn(
         dst: *mut c_void,
         src: *const c_void,
         ByteCount: size_t,
         hStream: CUstream,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    cuMemcpyPeer: unsafe extern "C" fn(
+        dstDevice: *mut c_void,
+        dstContext: CUcontext,
+        srcDevice: *const c_void,
+        srcContext: CUcontext,
+        ByteCount: size_t,
+    ) -> CUresultCode,
+    cuMemcpyPeerAsync: unsafe extern "C" fn(
+        dstDevice: *mut c_void,
+        dstContext: CUcontext,
+        srcDevice: *const c_void,
+        srcContext: CUcontext,
+        ByteCount: size_t,
+        hStream: CUstream,
+    ) -> CUresultCode,
+    #[symbol("cuMemsetD16_v2")]
+    cuMemsetD16:
+        unsafe extern "C" fn(dstDevice: *mut c_void, us: c_ushort, N: size_t) -> CUresultCode,
     cuMemsetD16Async: unsafe extern "C" fn(
         dstDevice: *mut c_void,
         us: c_ushort,
         N: size_t,
         hStream: CUstream,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuMemsetD32_v2")]
+    cuMemsetD32: unsafe extern "C" fn(dstDevice: *mut c_void, ui: c_uint, N: size_t) -> CUresultCode,
     cuMemsetD32Async: unsafe extern "C" fn(
         dstDevice: *mut c_void,
         ui: c_uint,
         N: size_t,
         hStream: CUstream,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuMemsetD8_v2")]
+    cuMemsetD8: unsafe extern "C" fn(dstDevice: *mut c_void, uc: c_uchar, N: size_t) -> CUresultCode,
     cuMemsetD8Async: unsafe extern "C" fn(
         dstDevice: *mut c_void,
         uc: c_uchar,
         N: size_t,
         hStream: CUstream,
-    ) -> CUresult,
+    ) -> CUresultCode,
     cuModuleGetFunction: unsafe extern "C" fn(
         hfunc: *mut CUfunction,
         hmod: CUmodule,
         name: *const c_char,
-    ) -> CUresult,
-    cuModuleLoadData: unsafe extern "C" fn(module: *mut CUmodule, image: *const c_void) -> CUresult,
-    cuModuleUnload: unsafe extern "C" fn(hmod: CUmodule) -> CUresult,
+    ) -> CUresultCode,
+    cuModuleLoadData: unsafe extern "C" fn(module: *mut CUmodule, image: *const c_void) -> CUresultCode,
+    cuModuleUnload: unsafe extern "C" fn(hmod: CUmodule) -> CUresultCode,
     cuOccupancyMaxPotentialBlockSize: unsafe extern "C" fn(
         minGridSize: *mut c_int,
         blockSize: *mut c_int,
@@ -467,45 +1022,62 @@ pub struct CudaApi {
         blockSizeToDynamicSMemSize: *mut c_void,
         dynamicSMemSize: size_t,
         blockSizeLimit: c_int,
-    ) -> CUresult,
-    cuCtxPushCurrent: unsafe extern "C" fn(ctx: CUcontext) -> CUresult,
-    cuCtxPopCurrent: unsafe extern "C" fn(pctx: *mut CUcontext) -> CUresult,
-    cuStreamCreate: unsafe extern "C" fn(phStream: *mut CUstream, Flags: c_uint) -> CUresult,
-    cuStreamDestroy: unsafe extern "C" fn(hStream: CUstream) -> CUresult,
-    cuStreamSynchronize: unsafe extern "C" fn(hStream: CUstream) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuCtxPushCurrent_v2")]
+    cuCtxPushCurrent: unsafe extern "C" fn(ctx: CUcontext) -> CUresultCode,
+    #[symbol("cuCtxPopCurrent_v2")]
+    cuCtxPopCurrent: unsafe extern "C" fn(pctx: *mut CUcontext) -> CUresultCode,
+    cuStreamCreate: unsafe extern "C" fn(phStream: *mut CUstream, Flags: c_uint) -> CUresultCode,
+    cuStreamCreateWithPriority: unsafe extern "C" fn(
+        phStream: *mut CUstream,
+        flags: c_uint,
+        priority: c_int,
+    ) -> CUresultCode,
+    #[symbol("cuStreamDestroy_v2")]
+    cuStreamDestroy: unsafe extern "C" fn(hStream: CUstream) -> CUresultCode,
+    cuStreamGetPriority: unsafe extern "C" fn(hStream: CUstream, priority: *mut c_int) -> CUresultCode,
+    cuStreamQuery: unsafe extern "C" fn(hStream: CUstream) -> CUresultCode,
+    cuStreamSynchronize: unsafe extern "C" fn(hStream: CUstream) -> CUresultCode,
     cuStreamWaitEvent:
-        unsafe extern "C" fn(hStream: CUstream, hEvent: CUevent, Flags: c_uint) -> CUresult,
+        unsafe extern "C" fn(hStream: CUstream, hEvent: CUevent, Flags: c_uint) -> CUresultCode,
+    #[since(11020)]
     cuMemAllocAsync: unsafe extern "C" fn(
         dptr: *mut CUdeviceptr,
         bytesize: size_t,
         hStream: CUstream,
-    ) -> CUresult,
-    cuMemFreeAsync: unsafe extern "C" fn(dptr: CUdeviceptr, hStream: CUstream) -> CUresult,
+    ) -> CUresultCode,
+    #[since(11020)]
+    cuMemFreeAsync: unsafe extern "C" fn(dptr: CUdeviceptr, hStream: CUstream) -> CUresultCode,
 
+    #[symbol("cuArrayCreate_v2")]
     cuArrayCreate: unsafe extern "C" fn(
         pHanlde: *mut CUarray,
         pAllocateArray: *const CUDA_ARRAY_DESCRIPTOR,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuArray3DCreate_v2")]
     cuArray3DCreate: unsafe extern "C" fn(
         pHandle: *mut CUarray,
         pAllocateArray: *const CUDA_ARRAY3D_DESCRIPTOR,
-    ) -> CUresult,
+    ) -> CUresultCode,
+    #[symbol("cuArray3DGetDescriptor_v2")]
     cuArray3DGetDescriptor: unsafe extern "C" fn(
         pArrayDescriptor: *mut CUDA_ARRAY3D_DESCRIPTOR,
         hArray: CUarray,
-    ) -> CUresult,
-    cuArrayDestroy: unsafe extern "C" fn(hArray: CUarray) -> CUresult,
+    ) -> CUresultCode,
+    cuArrayDestroy: unsafe extern "C" fn(hArray: CUarray) -> CUresultCode,
     cuTexObjectCreate: unsafe extern "C" fn(
         pTexObject: *mut CUtexObject,
         pResDesc: *const CUDA_RESOURCE_DESC,
         pTexDesc: *const CUDA_TEXTURE_DESC,
         pResViewDesc: *const CUDA_RESOURCE_VIEW_DESC,
-    ) -> CUresult,
-    cuTexObjectDestroy: unsafe extern "C" fn(texObject: CUtexObject) -> CUresult,
+    ) -> CUresultCode,
+    cuTexObjectDestroy: unsafe extern "C" fn(texObject: CUtexObject) -> CUresultCode,
     cuTexObjectGetResourceDesc:
-        unsafe extern "C" fn(pResDesc: *mut CUDA_RESOURCE_DESC, texObject: CUtexObject) -> CUresult,
+        unsafe extern "C" fn(pResDesc: *mut CUDA_RESOURCE_DESC, texObject: CUtexObject) -> CUresultCode,
+    #[symbol("cuMemcpy3DAsync_v2")]
     cuMemcpy3DAsync:
-        unsafe extern "C" fn(pCopy: *const CUDA_MEMCPY3D, hStream: CUstream) -> CUresult,
+        unsafe extern "C" fn(pCopy: *const CUDA_MEMCPY3D, hStream: CUstream) -> CUresultCode,
+    #[symbol("cuMemcpy2DAsync_v2")]
     cuMemcpy2DAsync:
-        unsafe extern "C" fn(pCopy: *const CUDA_MEMCPY2D, hStream: CUstream) -> CUresult,
+        unsafe extern "C" fn(pCopy: *const CUDA_MEMCPY2D, hStream: CUstream) -> CUresultCode,
 }
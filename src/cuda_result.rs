@@ -1,28 +1,179 @@
+use std::fmt::{self, Display};
+use std::panic::Location;
+
+use crate::compile::CompileError;
 use crate::cuda_api::CUresult;
+use crate::loader::LoadAttempt;
+
+/// A failed driver call.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    /// The entry point that failed.
+    pub function: &'static str,
+    pub result: CUresult,
+    /// Name and description of `result` as reported by the driver.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Where the call was made.
+    pub location: &'static Location<'static>,
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed at {}:{}: ",
+            self.function,
+            self.location.file(),
+            self.location.line()
+        )?;
+        let name = self
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{:?}", self.result));
+        match &self.description {
+            Some(description) => write!(f, "{description} ({name})"),
+            None => write!(f, "{name}"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// A driver call that failed with a code unknown to [`CUresult`], e.g. one added by a newer
+/// driver.
+#[derive(Debug, Clone)]
+pub struct UnknownResultError {
+    /// The entry point that failed.
+    pub function: &'static str,
+    pub code: i32,
+    /// Name of `code` as reported by the driver.
+    pub name: Option<String>,
+    /// Where the call was made.
+    pub location: &'static Location<'static>,
+}
+
+impl Display for UnknownResultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed at {}:{}: unknown result {} ({})",
+            self.function,
+            self.location.file(),
+            self.location.line(),
+            self.code,
+            self.name.as_deref().unwrap_or("no name")
+        )
+    }
+}
+
+impl std::error::Error for UnknownResultError {}
 
 #[derive(Debug, thiserror::Error)]
 #[allow(non_camel_case_types)]
 #[repr(i32)]
 pub enum CUError {
-    #[error("CUDA Result {:?}!", .0)]
-    CUResult(CUresult),
-    #[error("Unknown CUDA Result {}", .0)]
-    CUUnknownResult(i32),
+    #[error("{0}")]
+    CUResult(ApiError),
+    #[error("{0}")]
+    CUUnknownResult(UnknownResultError),
     #[error("Unsupported CUDA version!")]
     CUDAVersion,
+    #[error("Could not load the CUDA driver, tried: {}!", format_attempts(.0))]
+    DriverNotFound(Vec<LoadAttempt>),
+    #[error("{function} is not supported by driver {}.{}{}!", .version.0, .version.1, format_required(.required))]
+    Unsupported {
+        function: &'static str,
+        version: (i32, i32),
+        required: Option<(i32, i32)>,
+    },
+    #[error("Device {0} is poisoned by the sticky error {1:?} and has to be recovered!")]
+    Poisoned(i32, CUresult),
+    #[error("The resource was invalidated by a reset of device {0}!")]
+    Invalidated(i32),
+    #[error("Allocating {requested} bytes on device {device} exceeds its memory limit ({allocated} of {limit} bytes in use)!")]
+    OutOfMemory {
+        device: i32,
+        requested: usize,
+        allocated: usize,
+        limit: usize,
+    },
+    #[error("{len} elements of {element} bytes exceed the address space!")]
+    AllocationOverflow { len: usize, element: usize },
+    #[error("The module has no function {0}!")]
+    FunctionNotFound(String),
+    #[error("{function} requests {requested} bytes of dynamic shared memory, but only {available} are available!")]
+    SharedMemoryExceeded {
+        function: String,
+        requested: usize,
+        available: usize,
+    },
+    #[error("{function} is launched with {requested} bytes of dynamic shared memory, but allows only {max}, raise it with set_max_dynamic_shared_memory!")]
+    SharedMemoryNotOptedIn {
+        function: String,
+        requested: usize,
+        max: usize,
+    },
+    #[error("Cannot copy {src} bytes into {dst} bytes!")]
+    SizeMismatch { dst: usize, src: usize },
+    #[error("Accessing {len} bytes at offset {offset} exceeds the buffer of {size} bytes!")]
+    OutOfBounds {
+        offset: usize,
+        len: usize,
+        size: usize,
+    },
+    #[error("A stream of device {stream} cannot be used for work on device {device}!")]
+    StreamDeviceMismatch { stream: i32, device: i32 },
+    #[error("A buffer of device {src} cannot be copied within device {dst}, copy it from the peer instead!")]
+    BufferDeviceMismatch { dst: i32, src: i32 },
+    #[error("Device {0} cannot access the memory of device {1}!")]
+    PeerAccessUnsupported(i32, i32),
+    #[error("{0}")]
+    Compile(CompileError),
     #[error("No Device Found!")]
     NoDevice,
+    #[error("No device matches {0}!")]
+    NoMatchingDevice(String),
     #[error("Unknown Error!")]
     Unknown,
 }
 
+fn format_attempts(attempts: &[LoadAttempt]) -> String {
+    attempts
+        .iter()
+        .map(|attempt| format!("{} ({})", attempt.path.display(), attempt.error))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_required(required: &Option<(i32, i32)>) -> String {
+    required
+        .map(|(major, minor)| format!(" (requires {major}.{minor})"))
+        .unwrap_or_default()
+}
+
 pub type Result<T> = std::result::Result<T, CUError>;
 
-impl From<CUresult> for Result<()> {
-    fn from(value: CUresult) -> Self {
-        match value {
-            CUresult::CUDA_SUCCESS => Ok(()),
-            _ => Err(CUError::CUResult(value)),
+impl CUError {
+    /// The driver result behind this error, if it stems from a driver call.
+    pub fn result(&self) -> Option<CUresult> {
+        match self {
+            CUError::CUResult(error) => Some(error.result),
+            CUError::Poisoned(_, result) => Some(*result),
+            CUError::Compile(error) => error.error.result(),
+            _ => None,
         }
     }
+
+    /// Whether an allocation failed for lack of memory, either on the device or because of
+    /// the memory limit of the [`crate::cuda::Device`].
+    pub fn is_out_of_memory(&self) -> bool {
+        matches!(self, CUError::OutOfMemory { .. })
+            || self.result() == Some(CUresult::CUDA_ERROR_OUT_OF_MEMORY)
+    }
+
+    /// Whether the error left the context unusable, see [`CUresult::is_sticky`].
+    pub fn is_sticky(&self) -> bool {
+        self.result().is_some_and(CUresult::is_sticky)
+    }
 }
@@ -0,0 +1,168 @@
+//! Events for synchronizing on and timing work queued on a [`Stream`].
+
+use std::ptr::null;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cuda::Device;
+use crate::cuda_api::*;
+use crate::cuda_result::*;
+use crate::stream::Stream;
+
+/// A marker in the work queued on a [`Stream`], see [`Event::record`].
+///
+/// The event is destroyed when dropped.
+pub struct Event {
+    device: Arc<Device>,
+    event: CUevent,
+    timing: bool,
+    /// The [`Device::generation`] the event was created in.
+    generation: u64,
+}
+
+// SAFETY: Event handles can be used from any thread, the context is made current per call.
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
+impl Event {
+    /// Creates an event, which records timestamps if `timing` is set.
+    ///
+    /// Events without timing are cheaper to record and synchronize on.
+    pub fn create(device: &Arc<Device>, timing: bool) -> Result<Self> {
+        let flags = if timing {
+            CU_EVENT_DEFAULT
+        } else {
+            CU_EVENT_DISABLE_TIMING
+        };
+        let mut event: CUevent = null();
+        device.call(|cuda| unsafe { cuda.cuEventCreate(&mut event, flags as _) })?;
+        Ok(Self {
+            device: device.clone(),
+            event,
+            timing,
+            generation: device.generation(),
+        })
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    /// Whether the event records timestamps.
+    pub fn timing(&self) -> bool {
+        self.timing
+    }
+
+    /// The raw handle, e.g. to pass to other CUDA libraries.
+    pub fn as_raw(&self) -> CUevent {
+        self.event
+    }
+
+    /// Whether the event still exists, i.e. the device has not been recovered since.
+    pub fn is_valid(&self) -> bool {
+        self.generation == self.device.generation()
+    }
+
+    fn check_valid(&self) -> Result<()> {
+        if !self.is_valid() {
+            return Err(CUError::Invalidated(self.device.id));
+        }
+        Ok(())
+    }
+
+    /// Captures the work queued on `stream` so far. The event completes once that work has.
+    ///
+    /// Recording again replaces the captured work.
+    pub fn record(&self, stream: &Stream) -> Result<()> {
+        self.record_raw(stream.handle_for(&self.device)?)
+    }
+
+    /// Records on a stream handle of the device, e.g. the legacy default stream.
+    pub(crate) fn record_raw(&self, stream: CUstream) -> Result<()> {
+        self.check_valid()?;
+        self.device
+            .call(|cuda| unsafe { cuda.cuEventRecord(self.event, stream) })
+    }
+
+    /// Waits until the captured work has completed.
+    pub fn synchronize(&self) -> Result<()> {
+        self.check_valid()?;
+        self.device
+            .call(|cuda| unsafe { cuda.cuEventSynchronize(self.event) })
+    }
+
+    /// Returns whether the captured work has completed, without blocking.
+    pub fn query(&self) -> Result<bool> {
+        self.check_valid()?;
+        self.device.check_poisoned()?;
+        let _context = self.device.activate()?;
+        let result = unsafe { self.device.cuda.cuEventQuery(self.event) };
+        if result.result() == Ok(CUresult::CUDA_ERROR_NOT_READY) {
+            return Ok(false);
+        }
+        self.device.check(result)?;
+        Ok(true)
+    }
+
+    /// The GPU time between the completion of `start` and of this event.
+    ///
+    /// Both events need timing and must have completed, the resolution is about half a
+    /// microsecond.
+    pub fn elapsed_since(&self, start: &Event) -> Result<Duration> {
+        self.check_valid()?;
+        start.check_valid()?;
+        let mut milliseconds = 0.0;
+        self.device.call(|cuda| unsafe {
+            cuda.cuEventElapsedTime(&mut milliseconds, start.event, self.event)
+        })?;
+        Ok(Duration::from_secs_f64(
+            f64::from(milliseconds.max(0.0)) / 1e3,
+        ))
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        // Events of an earlier generation have been destroyed by the reset already.
+        if self.check_valid().is_ok() {
+            let _ = self
+                .device
+                .call(|cuda| unsafe { cuda.cuEventDestroy(self.event) });
+        }
+    }
+}
+
+/// Measures the GPU time of the work queued on a [`Stream`] between [`GpuTimer::start`] and
+/// [`GpuTimer::stop`].
+pub struct GpuTimer<'a> {
+    stream: &'a Stream,
+    start: Event,
+    end: Event,
+}
+
+impl<'a> GpuTimer<'a> {
+    /// Records the start of the measurement on `stream`.
+    pub fn start(stream: &'a Stream) -> Result<Self> {
+        let start = Event::create(stream.device(), true)?;
+        let end = Event::create(stream.device(), true)?;
+        start.record(stream)?;
+        Ok(Self { stream, start, end })
+    }
+
+    /// Records the end of the measurement and waits for the measured work to complete.
+    pub fn stop(self) -> Result<Duration> {
+        self.end.record(self.stream)?;
+        self.end.synchronize()?;
+        self.end.elapsed_since(&self.start)
+    }
+
+    /// Times the work that `f` queues on `stream`.
+    pub fn measure<R>(
+        stream: &'a Stream,
+        f: impl FnOnce(&'a Stream) -> Result<R>,
+    ) -> Result<(R, Duration)> {
+        let timer = Self::start(stream)?;
+        let result = f(stream)?;
+        Ok((result, timer.stop()?))
+    }
+}
@@ -0,0 +1,1351 @@
+//! A pure-Rust stand-in for the CUDA driver.
+//!
+//! [`FakeDriver`] implements [`Driver`] on top of host memory so that the safe layer can be
+//! exercised without a GPU. Every call is recorded and device attributes are configurable.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_char, c_float, c_int, c_uchar, c_uint, c_ushort, c_void, CStr, CString};
+use std::ptr::null;
+use std::sync::Arc;
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+use tracing_mutex::parkinglot::{DebugMutex, DebugMutexGuard};
+
+use crate::cuda_api::*;
+
+/// Configuration of a device exposed by the [`FakeDriver`].
+#[derive(Debug, Clone)]
+pub struct FakeDevice {
+    pub name: String,
+    pub total_mem: usize,
+    pub attributes: HashMap<c_int, c_int>,
+}
+
+impl Default for FakeDevice {
+    fn default() -> Self {
+        let attributes = [
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_ASYNC_ENGINE_COUNT, 2),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_CAN_MAP_HOST_MEMORY, 1),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_CLOCK_RATE, 1710000),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_CONCURRENT_KERNELS, 1),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_CONCURRENT_MANAGED_ACCESS,
+                1,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_COOPERATIVE_LAUNCH, 1),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_GLOBAL_MEMORY_BUS_WIDTH,
+                320,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_L2_CACHE_SIZE, 5242880),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MANAGED_MEMORY, 1),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_X, 1024),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Y, 1024),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Z, 64),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_BLOCKS_PER_MULTIPROCESSOR,
+                16,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_X, i32::MAX),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Y, 65535),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Z, 65535),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_BLOCK,
+                65536,
+            ),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_MULTIPROCESSOR,
+                65536,
+            ),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK,
+                49152,
+            ),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_MULTIPROCESSOR,
+                102400,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_BLOCK, 1024),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_MULTIPROCESSOR,
+                1536,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MEMORY_CLOCK_RATE, 9501000),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_STREAM_PRIORITIES_SUPPORTED,
+                1,
+            ),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_TOTAL_CONSTANT_MEMORY,
+                65536,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_WARP_SIZE, 32),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MAJOR, 8),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MINOR, 6),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN,
+                101376,
+            ),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED, 1),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT, 82),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_BUS_ID, 1),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_DEVICE_ID, 0),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_DOMAIN_ID, 0),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_UNIFIED_ADDRESSING, 1),
+            (CUAttribute::CU_DEVICE_ATTRIBUTE_TCC_DRIVER, 0),
+            (
+                CUAttribute::CU_DEVICE_ATTRIBUTE_COMPUTE_PREEMPTION_SUPPORTED,
+                1,
+            ),
+        ]
+        .into_iter()
+        .map(|(attribute, value)| (attribute as c_int, value))
+        .collect();
+        Self {
+            name: "Fake CUDA Device".into(),
+            total_mem: 1 << 30,
+            attributes,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FakeState {
+    version: c_int,
+    devices: Vec<FakeDevice>,
+    calls: Vec<&'static str>,
+    failures: HashMap<&'static str, CUresultCode>,
+    /// Entry points the driver pretends not to export.
+    missing: HashSet<&'static str>,
+    /// Allocations keyed by their address.
+    allocations: HashMap<usize, Box<[u8]>>,
+    /// Reference counts of the primary contexts.
+    primary_contexts: HashMap<CUdevice, u32>,
+    /// Flags of the primary contexts.
+    primary_flags: HashMap<CUdevice, c_uint>,
+    /// Context stacks of every thread.
+    context_stacks: HashMap<ThreadId, Vec<usize>>,
+    /// Limits set per context.
+    limits: HashMap<(usize, c_int), usize>,
+    /// Pairs of devices that cannot access each other, all others can.
+    no_peer_access: HashSet<(CUdevice, CUdevice)>,
+    /// Pairs of contexts, the first of which has been granted access to the second.
+    peer_access: HashSet<(usize, usize)>,
+    error_strings: HashMap<(i32, bool), CString>,
+    /// Last handle given out for links, modules, streams and events.
+    handles: usize,
+    links: HashMap<usize, FakeLink>,
+    /// Loaded modules and their image, read as text.
+    modules: HashMap<usize, String>,
+    functions: HashMap<usize, FakeFunction>,
+    /// Attributes of kernels, applied when they are looked up.
+    kernel_attributes: HashMap<String, HashMap<c_int, c_int>>,
+    launches: Vec<FakeLaunch>,
+    /// Flags and priorities of the streams.
+    streams: HashMap<usize, (c_uint, c_int)>,
+    events: HashMap<usize, FakeEvent>,
+    /// Host functions queued on a stream, which run once it is synchronized.
+    host_funcs: Vec<FakeHostFunc>,
+    /// The time that events are recorded at, see [`FakeDriver::advance_clock`].
+    clock: Duration,
+    /// Written to the info log of every link.
+    jit_info_log: String,
+    /// Written to the error log of the next link, whose compilation then fails.
+    jit_error_log: Option<String>,
+}
+
+/// An event, recorded at a time of the fake clock.
+struct FakeEvent {
+    timing: bool,
+    recorded: Option<Duration>,
+}
+
+/// A host function and its argument, queued on a stream.
+struct FakeHostFunc {
+    stream: usize,
+    func: unsafe extern "C" fn(*mut c_void),
+    data: usize,
+}
+
+/// A kernel looked up in a module.
+struct FakeFunction {
+    name: String,
+    attributes: HashMap<c_int, c_int>,
+}
+
+/// A kernel launch recorded by the [`FakeDriver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeLaunch {
+    pub function: String,
+    pub grid: [c_uint; 3],
+    pub block: [c_uint; 3],
+    pub shared_memory: c_uint,
+    pub stream: usize,
+}
+
+/// A link state, the "compiled" image is the concatenated input.
+#[derive(Default)]
+struct FakeLink {
+    info_log: Option<(usize, usize)>,
+    error_log: Option<(usize, usize)>,
+    image: Vec<u8>,
+}
+
+/// Writes `log` into a log buffer of the JIT compiler, truncating it if necessary.
+unsafe fn write_log(buffer: Option<(usize, usize)>, log: &str) {
+    if let Some((ptr, size)) = buffer.filter(|(ptr, size)| *ptr != 0 && *size > 0) {
+        let n = log.len().min(size - 1);
+        std::ptr::copy_nonoverlapping(log.as_ptr(), ptr as *mut u8, n);
+        *(ptr as *mut u8).add(n) = 0;
+    }
+}
+
+/// Least and greatest stream priority.
+const STREAM_PRIORITIES: (c_int, c_int) = (0, -5);
+
+/// Entry points that operate on the current context and fail without one.
+const CONTEXT_ENTRY_POINTS: &[&str] = &[
+    "cuCtxEnablePeerAccess",
+    "cuCtxGetLimit",
+    "cuCtxGetStreamPriorityRange",
+    "cuCtxSetLimit",
+    "cuCtxSynchronize",
+    "cuEventCreate",
+    "cuFuncGetAttribute",
+    "cuFuncSetAttribute",
+    "cuFuncSetCacheConfig",
+    "cuLaunchHostFunc",
+    "cuLaunchKernel",
+    "cuLinkAddData",
+    "cuLinkComplete",
+    "cuLinkCreate",
+    "cuMemAlloc",
+    "cuMemAllocHost",
+    "cuMemFree",
+    "cuMemFreeHost",
+    "cuMemGetInfo",
+    "cuMemcpy",
+    "cuMemcpyAsync",
+    "cuMemsetD16",
+    "cuMemsetD16Async",
+    "cuMemsetD32",
+    "cuMemsetD32Async",
+    "cuMemsetD8",
+    "cuMemsetD8Async",
+    "cuModuleGetFunction",
+    "cuModuleLoadData",
+    "cuModuleUnload",
+    "cuStreamCreate",
+    "cuStreamCreateWithPriority",
+];
+
+impl FakeState {
+    /// The context on top of the calling thread's stack.
+    fn current_context(&self) -> Option<usize> {
+        self.context_stacks
+            .get(&thread::current().id())
+            .and_then(|stack| stack.last())
+            .copied()
+    }
+
+    /// The device of the current context, which must exist.
+    fn current_device(&self) -> &FakeDevice {
+        // Primary contexts are numbered after their device.
+        &self.devices[self.current_context().unwrap() - 1]
+    }
+
+    /// Memory left on the device of the current context.
+    /// The fake does not track which context an allocation belongs to, so all of them count.
+    fn free_memory(&self) -> usize {
+        let allocated: usize = self.allocations.values().map(|a| a.len()).sum();
+        self.current_device().total_mem.saturating_sub(allocated)
+    }
+
+    fn handle(&mut self) -> usize {
+        self.handles += 1;
+        self.handles
+    }
+
+    /// Whether `stream` is the default stream or has been created and not destroyed yet.
+    fn check_stream(&self, stream: CUstream) -> bool {
+        stream.is_null() || self.streams.contains_key(&(stream as usize))
+    }
+
+    /// Checks that a copy of `len` bytes at `ptr` stays inside its allocation.
+    /// Pointers outside of any allocation are treated as host memory.
+    fn check_range(&self, ptr: usize, len: usize) -> bool {
+        self.allocations.iter().all(|(&start, allocation)| {
+            let end = start + allocation.len();
+            !(start..end).contains(&ptr) || ptr + len <= end
+        })
+    }
+}
+
+/// Records the call and evaluates to the locked state, returning early on injected failures.
+macro_rules! enter {
+    ($self:ident, $name:expr) => {
+        match $self.enter($name) {
+            Ok(state) => state,
+            Err(result) => return result,
+        }
+    };
+}
+
+/// An in-process driver backed by host memory.
+///
+/// Cloning yields another handle to the same driver, which allows inspecting it after it has
+/// been handed to [`crate::cuda::CUDA::with_driver`].
+#[derive(Clone)]
+pub struct FakeDriver {
+    state: Arc<DebugMutex<FakeState>>,
+}
+
+impl Default for FakeDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeDriver {
+    /// Creates a driver reporting CUDA 12.0 with a single default device.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(DebugMutex::new(FakeState {
+                version: 12000,
+                devices: vec![FakeDevice::default()],
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Sets the driver version in the encoding of `cuDriverGetVersion`, e.g. `11040` for 11.4.
+    pub fn with_version(self, version: c_int) -> Self {
+        self.lock().version = version;
+        self
+    }
+
+    /// Replaces the devices of the driver.
+    pub fn with_devices(self, devices: impl IntoIterator<Item = FakeDevice>) -> Self {
+        self.lock().devices = devices.into_iter().collect();
+        self
+    }
+
+    /// Pretends that the driver does not export the entry point `name`.
+    pub fn without(self, name: &'static str) -> Self {
+        self.lock().missing.insert(name);
+        self
+    }
+
+    /// Overrides a single attribute of device `dev`.
+    pub fn set_attribute(&self, dev: CUdevice, attribute: CUAttribute, value: c_int) {
+        self.lock().devices[dev as usize]
+            .attributes
+            .insert(attribute as c_int, value);
+    }
+
+    /// Prevents device `dev` from accessing the memory of device `peer`.
+    pub fn deny_peer_access(&self, dev: CUdevice, peer: CUdevice) {
+        self.lock().no_peer_access.insert((dev, peer));
+    }
+
+    /// Makes the next call of the entry point `name` fail with `result`.
+    pub fn fail_next(&self, name: &'static str, result: impl Into<CUresultCode>) {
+        self.lock().failures.insert(name, result.into());
+    }
+
+    /// Sets the info log reported by the JIT compiler.
+    pub fn set_jit_info_log(&self, log: &str) {
+        self.lock().jit_info_log = log.into();
+    }
+
+    /// Makes the next compilation fail with `log` as error log.
+    pub fn fail_jit(&self, log: &str) {
+        self.lock().jit_error_log = Some(log.into());
+    }
+
+    /// Names of all entry points called so far, in order.
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.lock().calls.clone()
+    }
+
+    /// Number of times the entry point `name` has been called.
+    pub fn call_count(&self, name: &str) -> usize {
+        self.lock()
+            .calls
+            .iter()
+            .filter(|call| **call == name)
+            .count()
+    }
+
+    /// Number of live device allocations.
+    pub fn allocation_count(&self) -> usize {
+        self.lock().allocations.len()
+    }
+
+    /// Overrides an attribute of every kernel called `name` looked up from now on.
+    pub fn set_kernel_attribute(&self, name: &str, attribute: CUFuncAttribute, value: c_int) {
+        self.lock()
+            .kernel_attributes
+            .entry(name.into())
+            .or_default()
+            .insert(attribute as c_int, value);
+    }
+
+    /// Number of streams that have not been destroyed.
+    pub fn stream_count(&self) -> usize {
+        self.lock().streams.len()
+    }
+
+    /// Number of events that have not been destroyed.
+    pub fn event_count(&self) -> usize {
+        self.lock().events.len()
+    }
+
+    /// Advances the time that events are recorded at, e.g. to simulate a kernel taking `by`.
+    pub fn advance_clock(&self, by: Duration) {
+        self.lock().clock += by;
+    }
+
+    /// All kernel launches so far, in order.
+    pub fn launches(&self) -> Vec<FakeLaunch> {
+        self.lock().launches.clone()
+    }
+
+    /// Returns a copy of the allocation starting at `ptr`.
+    pub fn memory(&self, ptr: *const c_void) -> Option<Vec<u8>> {
+        self.lock()
+            .allocations
+            .get(&(ptr as usize))
+            .map(|allocation| allocation.to_vec())
+    }
+
+    fn lock(&self) -> DebugMutexGuard<'_, FakeState> {
+        self.state.lock()
+    }
+
+    /// Records a call to `name` and returns the state, or the failure injected for it.
+    fn enter(
+        &self,
+        name: &'static str,
+    ) -> std::result::Result<DebugMutexGuard<'_, FakeState>, CUresultCode> {
+        let mut state = self.lock();
+        state.calls.push(name);
+        if state.missing.contains(name) {
+            return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED.into());
+        }
+        if let Some(result) = state.failures.remove(name) {
+            return Err(result);
+        }
+        if CONTEXT_ENTRY_POINTS.contains(&name) && state.current_context().is_none() {
+            return Err(CUresult::CUDA_ERROR_INVALID_CONTEXT.into());
+        }
+        Ok(state)
+    }
+
+    fn error_str(
+        &self,
+        error: CUresultCode,
+        description: bool,
+        pStr: *mut *const c_char,
+    ) -> CUresultCode {
+        // Like the driver, reject codes it does not know.
+        let Ok(error) = error.get() else {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        };
+        let mut state = self.lock();
+        let string = state
+            .error_strings
+            .entry((error as i32, description))
+            .or_insert_with(|| {
+                let name = format!("{error:?}");
+                let string = if description {
+                    name.trim_start_matches("CUDA_ERROR_")
+                        .trim_start_matches("CUDA_")
+                        .replace('_', " ")
+                        .to_lowercase()
+                } else {
+                    name
+                };
+                CString::new(string).unwrap()
+            });
+        unsafe { *pStr = string.as_ptr() };
+        CUresult::CUDA_SUCCESS.into()
+    }
+
+    /// Copies `len` bytes at once, work queued on streams completes immediately.
+    unsafe fn copy(
+        &self,
+        name: &'static str,
+        dst: *mut c_void,
+        src: *const c_void,
+        len: usize,
+        stream: CUstream,
+    ) -> CUresultCode {
+        let state = enter!(self, name);
+        if !state.check_stream(stream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        if !state.check_range(dst as usize, len) || !state.check_range(src as usize, len) {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        }
+        std::ptr::copy(src as *const u8, dst as *mut u8, len);
+        CUresult::CUDA_SUCCESS.into()
+    }
+
+    fn memset(
+        &self,
+        name: &'static str,
+        dst: *mut c_void,
+        value: &[u8],
+        N: usize,
+        stream: CUstream,
+    ) -> CUresultCode {
+        let state = enter!(self, name);
+        if !state.check_stream(stream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        if !state.check_range(dst as usize, value.len() * N) {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        }
+        for i in 0..N {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    value.as_ptr(),
+                    (dst as *mut u8).add(i * value.len()),
+                    value.len(),
+                )
+            };
+        }
+        CUresult::CUDA_SUCCESS.into()
+    }
+
+    /// Runs the pending host functions of the streams that `select` picks, in order.
+    unsafe fn run_host_funcs(
+        mut state: DebugMutexGuard<'_, FakeState>,
+        select: impl Fn(usize) -> bool,
+    ) {
+        let (selected, pending) = state
+            .host_funcs
+            .drain(..)
+            .partition::<Vec<_>, _>(|host_func| select(host_func.stream));
+        state.host_funcs = pending;
+        // The functions may call back into the driver.
+        drop(state);
+        for host_func in selected {
+            (host_func.func)(host_func.data as *mut c_void);
+        }
+    }
+
+    /// Creates a stream, clamping `priority` like the driver does.
+    unsafe fn create_stream(
+        &self,
+        name: &'static str,
+        phStream: *mut CUstream,
+        flags: c_uint,
+        priority: c_int,
+    ) -> CUresultCode {
+        let mut state = enter!(self, name);
+        if flags & !(CU_STREAM_NON_BLOCKING as c_uint) != 0 {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        }
+        let priority = priority.clamp(STREAM_PRIORITIES.1, STREAM_PRIORITIES.0);
+        let handle = state.handle();
+        state.streams.insert(handle, (flags, priority));
+        *phStream = handle as CUstream;
+        CUresult::CUDA_SUCCESS.into()
+    }
+
+    fn event_complete(&self, name: &'static str, event: CUevent) -> CUresultCode {
+        let state = enter!(self, name);
+        if !state.events.contains_key(&(event as usize)) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        CUresult::CUDA_SUCCESS.into()
+    }
+}
+
+impl Driver for FakeDriver {
+    fn unsupported(&self, name: &'static str) -> CUresultCode {
+        let _state = enter!(self, name);
+        CUresult::CUDA_ERROR_NOT_SUPPORTED.into()
+    }
+    fn has_entry_point(&self, name: &str) -> bool {
+        !self.lock().missing.contains(name)
+    }
+
+    unsafe fn cuInit(&self, Flags: c_uint) -> CUresultCode {
+        let _state = enter!(self, "cuInit");
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDriverGetVersion(&self, driverVersion: *mut c_int) -> CUresultCode {
+        let state = enter!(self, "cuDriverGetVersion");
+        *driverVersion = state.version;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDeviceGetCount(&self, count: *mut c_int) -> CUresultCode {
+        let state = enter!(self, "cuDeviceGetCount");
+        *count = state.devices.len() as c_int;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDeviceGet(&self, device: *mut CUdevice, ordinal: c_int) -> CUresultCode {
+        let state = enter!(self, "cuDeviceGet");
+        if ordinal < 0 || ordinal as usize >= state.devices.len() {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        *device = ordinal;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDeviceGetName(&self, name: *mut c_char, len: c_int, dev: CUdevice) -> CUresultCode {
+        let state = enter!(self, "cuDeviceGetName");
+        let Some(device) = state.devices.get(dev as usize) else {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        };
+        let bytes = device.name.as_bytes();
+        let n = bytes.len().min(len as usize - 1);
+        std::ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, name, n);
+        *name.add(n) = 0;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    /// Every fake device has the UUID `fa4e0000-0000-0000-0000-0000000000xx`, `xx` being its
+    /// ordinal.
+    unsafe fn cuDeviceGetUuid(&self, uuid: *mut CUuuid, dev: CUdevice) -> CUresultCode {
+        let state = enter!(self, "cuDeviceGetUuid");
+        if dev < 0 || dev as usize >= state.devices.len() {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        let mut bytes = [0; 16];
+        bytes[..2].copy_from_slice(&[0xfa, 0x4e]);
+        bytes[15] = dev as c_uchar;
+        *uuid = CUuuid { bytes };
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDeviceGetAttribute(
+        &self,
+        pi: *mut c_int,
+        attrib: c_int,
+        dev: CUdevice,
+    ) -> CUresultCode {
+        let state = enter!(self, "cuDeviceGetAttribute");
+        let Some(device) = state.devices.get(dev as usize) else {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        };
+        *pi = device.attributes.get(&attrib).copied().unwrap_or(0);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDeviceTotalMem(&self, bytes: *mut size_t, dev: CUdevice) -> CUresultCode {
+        let state = enter!(self, "cuDeviceTotalMem");
+        let Some(device) = state.devices.get(dev as usize) else {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        };
+        *bytes = device.total_mem as size_t;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDevicePrimaryCtxRetain(&self, pctx: *mut CUcontext, dev: CUdevice) -> CUresultCode {
+        let mut state = enter!(self, "cuDevicePrimaryCtxRetain");
+        if dev < 0 || dev as usize >= state.devices.len() {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        *state.primary_contexts.entry(dev).or_default() += 1;
+        // Contexts are never dereferenced, any unique non-null value will do.
+        *pctx = (dev as usize + 1) as CUcontext;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDevicePrimaryCtxRelease(&self, dev: CUdevice) -> CUresultCode {
+        let mut state = enter!(self, "cuDevicePrimaryCtxRelease");
+        match state.primary_contexts.get_mut(&dev) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                CUresult::CUDA_SUCCESS.into()
+            }
+            _ => CUresult::CUDA_ERROR_INVALID_CONTEXT.into(),
+        }
+    }
+    unsafe fn cuDevicePrimaryCtxGetState(
+        &self,
+        dev: CUdevice,
+        flags: *mut c_uint,
+        active: *mut c_int,
+    ) -> CUresultCode {
+        let state = enter!(self, "cuDevicePrimaryCtxGetState");
+        if dev < 0 || dev as usize >= state.devices.len() {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        *flags = state.primary_flags.get(&dev).copied().unwrap_or_default();
+        *active = (state
+            .primary_contexts
+            .get(&dev)
+            .copied()
+            .unwrap_or_default()
+            > 0) as c_int;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDevicePrimaryCtxSetFlags(&self, dev: CUdevice, flags: c_uint) -> CUresultCode {
+        let mut state = enter!(self, "cuDevicePrimaryCtxSetFlags");
+        if dev < 0 || dev as usize >= state.devices.len() {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        let scheduling = flags & CU_CTX_SCHED_MASK;
+        if flags & !(CU_CTX_SCHED_MASK | CU_CTX_LMEM_RESIZE_TO_MAX) != 0
+            || scheduling.count_ones() > 1
+        {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        }
+        state.primary_flags.insert(dev, flags);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuDevicePrimaryCtxReset(&self, dev: CUdevice) -> CUresultCode {
+        let mut state = enter!(self, "cuDevicePrimaryCtxReset");
+        if dev < 0 || dev as usize >= state.devices.len() {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        // The fake does not track which context an allocation belongs to.
+        state.allocations.clear();
+        // Like the driver, drop pending host functions without running them.
+        state.host_funcs.clear();
+        let context = dev as usize + 1;
+        state.limits.retain(|&(ctx, _), _| ctx != context);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuCtxPushCurrent(&self, ctx: CUcontext) -> CUresultCode {
+        let mut state = enter!(self, "cuCtxPushCurrent");
+        state
+            .context_stacks
+            .entry(thread::current().id())
+            .or_default()
+            .push(ctx as usize);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuCtxPopCurrent(&self, pctx: *mut CUcontext) -> CUresultCode {
+        let mut state = enter!(self, "cuCtxPopCurrent");
+        let stack = state
+            .context_stacks
+            .entry(thread::current().id())
+            .or_default();
+        match stack.pop() {
+            Some(ctx) => {
+                if !pctx.is_null() {
+                    *pctx = ctx as CUcontext;
+                }
+                CUresult::CUDA_SUCCESS.into()
+            }
+            None => CUresult::CUDA_ERROR_INVALID_CONTEXT.into(),
+        }
+    }
+    unsafe fn cuDeviceCanAccessPeer(
+        &self,
+        canAccessPeer: *mut c_int,
+        dev: CUdevice,
+        peerDev: CUdevice,
+    ) -> CUresultCode {
+        let state = enter!(self, "cuDeviceCanAccessPeer");
+        let count = state.devices.len() as CUdevice;
+        if !(0..count).contains(&dev) || !(0..count).contains(&peerDev) {
+            return CUresult::CUDA_ERROR_INVALID_DEVICE.into();
+        }
+        *canAccessPeer =
+            (dev != peerDev && !state.no_peer_access.contains(&(dev, peerDev))) as c_int;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuCtxEnablePeerAccess(&self, peerContext: CUcontext, Flags: c_uint) -> CUresultCode {
+        let mut state = enter!(self, "cuCtxEnablePeerAccess");
+        let Some(ctx) = state.current_context() else {
+            return CUresult::CUDA_ERROR_INVALID_CONTEXT.into();
+        };
+        let peer = peerContext as usize;
+        // Primary contexts are numbered after their device.
+        let (dev, peer_dev) = (ctx as CUdevice - 1, peer as CUdevice - 1);
+        if dev == peer_dev || state.no_peer_access.contains(&(dev, peer_dev)) {
+            return CUresult::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED.into();
+        }
+        if !state.peer_access.insert((ctx, peer)) {
+            return CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED.into();
+        }
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuCtxGetLimit(&self, pvalue: *mut size_t, limit: c_int) -> CUresultCode {
+        let state = enter!(self, "cuCtxGetLimit");
+        let ctx = state.current_context().unwrap();
+        let default = match CUlimit::try_from(limit) {
+            Ok(CUlimit::CU_LIMIT_STACK_SIZE) => 1024,
+            Ok(CUlimit::CU_LIMIT_PRINTF_FIFO_SIZE) => 1 << 20,
+            Ok(CUlimit::CU_LIMIT_MALLOC_HEAP_SIZE) => 8 << 20,
+            Ok(CUlimit::CU_LIMIT_DEV_RUNTIME_SYNC_DEPTH) => 2,
+            Ok(CUlimit::CU_LIMIT_DEV_RUNTIME_PENDING_LAUNCH_COUNT) => 2048,
+            Ok(CUlimit::CU_LIMIT_MAX_L2_FETCH_GRANULARITY) => 64,
+            Ok(CUlimit::CU_LIMIT_PERSISTING_L2_CACHE_SIZE) => 0,
+            Err(_) => return CUresult::CUDA_ERROR_UNSUPPORTED_LIMIT.into(),
+        };
+        *pvalue = state.limits.get(&(ctx, limit)).copied().unwrap_or(default) as size_t;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuCtxSetLimit(&self, limit: c_int, value: size_t) -> CUresultCode {
+        let mut state = enter!(self, "cuCtxSetLimit");
+        let ctx = state.current_context().unwrap();
+        match CUlimit::try_from(limit) {
+            Ok(CUlimit::CU_LIMIT_MAX_L2_FETCH_GRANULARITY) if value > 128 => {
+                CUresult::CUDA_ERROR_INVALID_VALUE.into()
+            }
+            Ok(_) => {
+                state.limits.insert((ctx, limit), value as usize);
+                CUresult::CUDA_SUCCESS.into()
+            }
+            Err(_) => CUresult::CUDA_ERROR_UNSUPPORTED_LIMIT.into(),
+        }
+    }
+    unsafe fn cuCtxSynchronize(&self) -> CUresultCode {
+        let state = enter!(self, "cuCtxSynchronize");
+        Self::run_host_funcs(state, |_| true);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    /// Reports the range of Ampere, from 0 down to -5.
+    unsafe fn cuCtxGetStreamPriorityRange(
+        &self,
+        leastPriority: *mut c_int,
+        greatestPriority: *mut c_int,
+    ) -> CUresultCode {
+        let _state = enter!(self, "cuCtxGetStreamPriorityRange");
+        *leastPriority = STREAM_PRIORITIES.0;
+        *greatestPriority = STREAM_PRIORITIES.1;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuStreamCreate(&self, phStream: *mut CUstream, Flags: c_uint) -> CUresultCode {
+        self.create_stream("cuStreamCreate", phStream, Flags, STREAM_PRIORITIES.0)
+    }
+    unsafe fn cuStreamCreateWithPriority(
+        &self,
+        phStream: *mut CUstream,
+        flags: c_uint,
+        priority: c_int,
+    ) -> CUresultCode {
+        self.create_stream("cuStreamCreateWithPriority", phStream, flags, priority)
+    }
+    unsafe fn cuStreamDestroy(&self, hStream: CUstream) -> CUresultCode {
+        let mut state = enter!(self, "cuStreamDestroy");
+        if state.streams.remove(&(hStream as usize)).is_none() {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        // Pending work still completes.
+        Self::run_host_funcs(state, |stream| stream == hStream as usize);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuStreamGetPriority(&self, hStream: CUstream, priority: *mut c_int) -> CUresultCode {
+        let state = enter!(self, "cuStreamGetPriority");
+        if hStream.is_null() {
+            *priority = STREAM_PRIORITIES.0;
+            return CUresult::CUDA_SUCCESS.into();
+        }
+        match state.streams.get(&(hStream as usize)) {
+            Some(&(_, stream_priority)) => {
+                *priority = stream_priority;
+                CUresult::CUDA_SUCCESS.into()
+            }
+            None => CUresult::CUDA_ERROR_INVALID_HANDLE.into(),
+        }
+    }
+    /// Work completes immediately, so streams are idle unless host functions are pending.
+    unsafe fn cuStreamQuery(&self, hStream: CUstream) -> CUresultCode {
+        let state = enter!(self, "cuStreamQuery");
+        if !state.check_stream(hStream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        if state
+            .host_funcs
+            .iter()
+            .any(|host_func| host_func.stream == hStream as usize)
+        {
+            return CUresult::CUDA_ERROR_NOT_READY.into();
+        }
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuEventCreate(&self, phEvent: *mut CUevent, Flags: c_uint) -> CUresultCode {
+        let mut state = enter!(self, "cuEventCreate");
+        let handle = state.handle();
+        let event = FakeEvent {
+            timing: Flags & CU_EVENT_DISABLE_TIMING as c_uint == 0,
+            recorded: None,
+        };
+        state.events.insert(handle, event);
+        *phEvent = handle as CUevent;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuEventDestroy(&self, hEvent: CUevent) -> CUresultCode {
+        let mut state = enter!(self, "cuEventDestroy");
+        match state.events.remove(&(hEvent as usize)) {
+            Some(_) => CUresult::CUDA_SUCCESS.into(),
+            None => CUresult::CUDA_ERROR_INVALID_HANDLE.into(),
+        }
+    }
+    unsafe fn cuEventRecord(&self, hEvent: CUevent, hStream: CUstream) -> CUresultCode {
+        let mut state = enter!(self, "cuEventRecord");
+        if !state.check_stream(hStream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        let clock = state.clock;
+        match state.events.get_mut(&(hEvent as usize)) {
+            Some(event) => {
+                event.recorded = Some(clock);
+                CUresult::CUDA_SUCCESS.into()
+            }
+            None => CUresult::CUDA_ERROR_INVALID_HANDLE.into(),
+        }
+    }
+    /// Recorded work completes immediately, so events are always complete.
+    unsafe fn cuEventQuery(&self, hEvent: CUevent) -> CUresultCode {
+        self.event_complete("cuEventQuery", hEvent)
+    }
+    unsafe fn cuEventSynchronize(&self, hEvent: CUevent) -> CUresultCode {
+        self.event_complete("cuEventSynchronize", hEvent)
+    }
+    unsafe fn cuEventElapsedTime(
+        &self,
+        pMilliseconds: *mut c_float,
+        hStart: CUevent,
+        hEnd: CUevent,
+    ) -> CUresultCode {
+        let state = enter!(self, "cuEventElapsedTime");
+        let recorded = |event: CUevent| {
+            state
+                .events
+                .get(&(event as usize))
+                .filter(|event| event.timing)
+                .and_then(|event| event.recorded)
+        };
+        let (Some(start), Some(end)) = (recorded(hStart), recorded(hEnd)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        *pMilliseconds = (end.as_secs_f64() - start.as_secs_f64()) as c_float * 1e3;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuStreamWaitEvent(
+        &self,
+        hStream: CUstream,
+        hEvent: CUevent,
+        Flags: c_uint,
+    ) -> CUresultCode {
+        let state = enter!(self, "cuStreamWaitEvent");
+        if !state.check_stream(hStream) || !state.events.contains_key(&(hEvent as usize)) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        CUresult::CUDA_SUCCESS.into()
+    }
+    /// Queues the function until the stream is synchronized, like a lagging driver thread.
+    unsafe fn cuLaunchHostFunc(
+        &self,
+        hStream: CUstream,
+        func: unsafe extern "C" fn(*mut c_void),
+        userData: *mut c_void,
+    ) -> CUresultCode {
+        let mut state = enter!(self, "cuLaunchHostFunc");
+        if !state.check_stream(hStream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        state.host_funcs.push(FakeHostFunc {
+            stream: hStream as usize,
+            func,
+            data: userData as usize,
+        });
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuStreamSynchronize(&self, hStream: CUstream) -> CUresultCode {
+        let state = enter!(self, "cuStreamSynchronize");
+        if !state.check_stream(hStream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        Self::run_host_funcs(state, |stream| stream == hStream as usize);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuGetErrorName(&self, error: CUresultCode, pStr: *mut *const c_char) -> CUresultCode {
+        self.error_str(error, false, pStr)
+    }
+    unsafe fn cuGetErrorString(
+        &self,
+        error: CUresultCode,
+        pStr: *mut *const c_char,
+    ) -> CUresultCode {
+        self.error_str(error, true, pStr)
+    }
+    unsafe fn cuMemAlloc(&self, dptr: *mut *mut c_void, bytesize: size_t) -> CUresultCode {
+        let mut state = enter!(self, "cuMemAlloc");
+        if bytesize == 0 {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        }
+        if bytesize as usize > state.free_memory() {
+            return CUresult::CUDA_ERROR_OUT_OF_MEMORY.into();
+        }
+        let mut allocation = vec![0u8; bytesize as usize].into_boxed_slice();
+        *dptr = allocation.as_mut_ptr() as *mut c_void;
+        state.allocations.insert(*dptr as usize, allocation);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuMemGetInfo(&self, free: *mut size_t, total: *mut size_t) -> CUresultCode {
+        let state = enter!(self, "cuMemGetInfo");
+        *free = state.free_memory() as size_t;
+        *total = state.current_device().total_mem as size_t;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuMemFree(&self, dptr: *mut c_void) -> CUresultCode {
+        let mut state = enter!(self, "cuMemFree");
+        match state.allocations.remove(&(dptr as usize)) {
+            Some(_) => CUresult::CUDA_SUCCESS.into(),
+            None => CUresult::CUDA_ERROR_INVALID_VALUE.into(),
+        }
+    }
+    unsafe fn cuMemAllocHost(&self, pp: *mut *mut c_void, bytesize: size_t) -> CUresultCode {
+        self.cuMemAlloc(pp, bytesize)
+    }
+    unsafe fn cuMemFreeHost(&self, p: *mut c_void) -> CUresultCode {
+        self.cuMemFree(p)
+    }
+    unsafe fn cuMemcpy(
+        &self,
+        dst: *mut c_void,
+        src: *const c_void,
+        ByteCount: size_t,
+    ) -> CUresultCode {
+        self.copy("cuMemcpy", dst, src, ByteCount as usize, null())
+    }
+    unsafe fn cuMemcpyPeer(
+        &self,
+        dstDevice: *mut c_void,
+        dstContext: CUcontext,
+        srcDevice: *const c_void,
+        srcContext: CUcontext,
+        ByteCount: size_t,
+    ) -> CUresultCode {
+        self.copy(
+            "cuMemcpyPeer",
+            dstDevice,
+            srcDevice,
+            ByteCount as usize,
+            null(),
+        )
+    }
+    unsafe fn cuMemcpyPeerAsync(
+        &self,
+        dstDevice: *mut c_void,
+        dstContext: CUcontext,
+        srcDevice: *const c_void,
+        srcContext: CUcontext,
+        ByteCount: size_t,
+        hStream: CUstream,
+    ) -> CUresultCode {
+        self.copy(
+            "cuMemcpyPeerAsync",
+            dstDevice,
+            srcDevice,
+            ByteCount as usize,
+            hStream,
+        )
+    }
+    unsafe fn cuMemcpyAsync(
+        &self,
+        dst: *mut c_void,
+        src: *const c_void,
+        ByteCount: size_t,
+        hStream: CUstream,
+    ) -> CUresultCode {
+        self.copy("cuMemcpyAsync", dst, src, ByteCount as usize, hStream)
+    }
+    unsafe fn cuMemsetD8(&self, dstDevice: *mut c_void, uc: c_uchar, N: size_t) -> CUresultCode {
+        self.memset("cuMemsetD8", dstDevice, &[uc], N as usize, null())
+    }
+    unsafe fn cuMemsetD8Async(
+        &self,
+        dstDevice: *mut c_void,
+        uc: c_uchar,
+        N: size_t,
+        hStream: CUstream,
+    ) -> CUresultCode {
+        self.memset("cuMemsetD8Async", dstDevice, &[uc], N as usize, hStream)
+    }
+    unsafe fn cuMemsetD16(&self, dstDevice: *mut c_void, us: c_ushort, N: size_t) -> CUresultCode {
+        self.memset(
+            "cuMemsetD16",
+            dstDevice,
+            &us.to_ne_bytes(),
+            N as usize,
+            null(),
+        )
+    }
+    unsafe fn cuMemsetD16Async(
+        &self,
+        dstDevice: *mut c_void,
+        us: c_ushort,
+        N: size_t,
+        hStream: CUstream,
+    ) -> CUresultCode {
+        self.memset(
+            "cuMemsetD16Async",
+            dstDevice,
+            &us.to_ne_bytes(),
+            N as usize,
+            hStream,
+        )
+    }
+    unsafe fn cuMemsetD32(&self, dstDevice: *mut c_void, ui: c_uint, N: size_t) -> CUresultCode {
+        self.memset(
+            "cuMemsetD32",
+            dstDevice,
+            &ui.to_ne_bytes(),
+            N as usize,
+            null(),
+        )
+    }
+    unsafe fn cuMemsetD32Async(
+        &self,
+        dstDevice: *mut c_void,
+        ui: c_uint,
+        N: size_t,
+        hStream: CUstream,
+    ) -> CUresultCode {
+        self.memset(
+            "cuMemsetD32Async",
+            dstDevice,
+            &ui.to_ne_bytes(),
+            N as usize,
+            hStream,
+        )
+    }
+    unsafe fn cuLinkCreate(
+        &self,
+        numOptions: c_uint,
+        options: *mut c_int,
+        optionValues: *mut *mut c_void,
+        stateOut: *mut CUlinkState,
+    ) -> CUresultCode {
+        let mut state = enter!(self, "cuLinkCreate");
+        let mut link = FakeLink::default();
+        for i in 0..numOptions as usize {
+            let value = *optionValues.add(i) as usize;
+            match *options.add(i) {
+                CU_JIT_INFO_LOG_BUFFER => link.info_log.get_or_insert((0, 0)).0 = value,
+                CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES => link.info_log.get_or_insert((0, 0)).1 = value,
+                CU_JIT_ERROR_LOG_BUFFER => link.error_log.get_or_insert((0, 0)).0 = value,
+                CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES => {
+                    link.error_log.get_or_insert((0, 0)).1 = value
+                }
+                _ => {}
+            }
+        }
+        let handle = state.handle();
+        state.links.insert(handle, link);
+        *stateOut = handle as CUlinkState;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuLinkAddData(
+        &self,
+        state: CUlinkState,
+        ty: c_int,
+        data: *mut c_void,
+        size: size_t,
+        name: *const c_char,
+        numOptions: c_uint,
+        options: *mut c_int,
+        optionValues: *mut *mut c_void,
+    ) -> CUresultCode {
+        let mut fake = enter!(self, "cuLinkAddData");
+        let error_log = fake.jit_error_log.take();
+        let info_log = fake.jit_info_log.clone();
+        let Some(link) = fake.links.get_mut(&(state as usize)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        if let Some(log) = error_log {
+            write_log(link.error_log, &log);
+            return CUresult::CUDA_ERROR_INVALID_PTX.into();
+        }
+        write_log(link.info_log, &info_log);
+        link.image
+            .extend_from_slice(std::slice::from_raw_parts(data as *const u8, size as usize));
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuLinkComplete(
+        &self,
+        state: CUlinkState,
+        cubinOut: *mut *mut c_void,
+        sizeOut: *mut size_t,
+    ) -> CUresultCode {
+        let mut fake = enter!(self, "cuLinkComplete");
+        let Some(link) = fake.links.get_mut(&(state as usize)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        *cubinOut = link.image.as_mut_ptr() as *mut c_void;
+        *sizeOut = link.image.len() as size_t;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuLinkDestroy(&self, state: CUlinkState) -> CUresultCode {
+        let mut fake = enter!(self, "cuLinkDestroy");
+        match fake.links.remove(&(state as usize)) {
+            Some(_) => CUresult::CUDA_SUCCESS.into(),
+            None => CUresult::CUDA_ERROR_INVALID_HANDLE.into(),
+        }
+    }
+    unsafe fn cuModuleLoadData(&self, module: *mut CUmodule, image: *const c_void) -> CUresultCode {
+        let mut state = enter!(self, "cuModuleLoadData");
+        let image = CStr::from_ptr(image as *const c_char)
+            .to_string_lossy()
+            .into_owned();
+        let handle = state.handle();
+        state.modules.insert(handle, image);
+        *module = handle as CUmodule;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuModuleUnload(&self, hmod: CUmodule) -> CUresultCode {
+        let mut state = enter!(self, "cuModuleUnload");
+        match state.modules.remove(&(hmod as usize)) {
+            Some(_) => CUresult::CUDA_SUCCESS.into(),
+            None => CUresult::CUDA_ERROR_INVALID_HANDLE.into(),
+        }
+    }
+    /// Finds every kernel whose name appears in the image.
+    unsafe fn cuModuleGetFunction(
+        &self,
+        hfunc: *mut CUfunction,
+        hmod: CUmodule,
+        name: *const c_char,
+    ) -> CUresultCode {
+        let mut state = enter!(self, "cuModuleGetFunction");
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+        let Some(image) = state.modules.get(&(hmod as usize)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        if !image.contains(&name) {
+            return CUresult::CUDA_ERROR_NOT_FOUND.into();
+        }
+        let mut attributes: HashMap<c_int, c_int> = [
+            (
+                CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_THREADS_PER_BLOCK,
+                1024,
+            ),
+            (CUFuncAttribute::CU_FUNC_ATTRIBUTE_NUM_REGS, 32),
+            (
+                CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES,
+                48 << 10,
+            ),
+            (
+                CUFuncAttribute::CU_FUNC_ATTRIBUTE_PREFERRED_SHARED_MEMORY_CARVEOUT,
+                -1,
+            ),
+        ]
+        .into_iter()
+        .map(|(attribute, value)| (attribute as c_int, value))
+        .collect();
+        if let Some(overrides) = state.kernel_attributes.get(&name) {
+            attributes.extend(overrides);
+        }
+        let handle = state.handle();
+        state
+            .functions
+            .insert(handle, FakeFunction { name, attributes });
+        *hfunc = handle as CUfunction;
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuFuncGetAttribute(
+        &self,
+        pi: *mut c_int,
+        attrib: c_int,
+        hfunc: CUfunction,
+    ) -> CUresultCode {
+        let state = enter!(self, "cuFuncGetAttribute");
+        let Some(function) = state.functions.get(&(hfunc as usize)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        *pi = function.attributes.get(&attrib).copied().unwrap_or(0);
+        CUresult::CUDA_SUCCESS.into()
+    }
+    unsafe fn cuFuncSetAttribute(
+        &self,
+        hfunc: CUfunction,
+        attrib: c_int,
+        value: c_int,
+    ) -> CUresultCode {
+        let mut state = enter!(self, "cuFuncSetAttribute");
+        let optin = state
+            .current_device()
+            .attributes
+            .get(&(CUAttribute::CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN as c_int))
+            .copied()
+            .unwrap_or(0);
+        let Some(function) = state.functions.get_mut(&(hfunc as usize)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        let static_shared = function
+            .attributes
+            .get(&(CUFuncAttribute::CU_FUNC_ATTRIBUTE_SHARED_SIZE_BYTES as c_int))
+            .copied()
+            .unwrap_or(0);
+        match CUFuncAttribute::try_from(attrib) {
+            Ok(CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES)
+                if value < 0 || value + static_shared > optin =>
+            {
+                CUresult::CUDA_ERROR_INVALID_VALUE.into()
+            }
+            Ok(CUFuncAttribute::CU_FUNC_ATTRIBUTE_PREFERRED_SHARED_MEMORY_CARVEOUT)
+                if !(-1..=100).contains(&value) =>
+            {
+                CUresult::CUDA_ERROR_INVALID_VALUE.into()
+            }
+            Ok(_) => {
+                function.attributes.insert(attrib, value);
+                CUresult::CUDA_SUCCESS.into()
+            }
+            Err(_) => CUresult::CUDA_ERROR_INVALID_VALUE.into(),
+        }
+    }
+    unsafe fn cuFuncSetCacheConfig(&self, hfunc: CUfunction, config: c_int) -> CUresultCode {
+        let state = enter!(self, "cuFuncSetCacheConfig");
+        if !state.functions.contains_key(&(hfunc as usize)) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        match CUFuncCache::try_from(config) {
+            Ok(_) => CUresult::CUDA_SUCCESS.into(),
+            Err(_) => CUresult::CUDA_ERROR_INVALID_VALUE.into(),
+        }
+    }
+    /// Records the launch without running anything.
+    unsafe fn cuLaunchKernel(
+        &self,
+        f: CUfunction,
+        gridDimX: c_uint,
+        gridDimY: c_uint,
+        gridDimZ: c_uint,
+        blockDimX: c_uint,
+        blockDimY: c_uint,
+        blockDimZ: c_uint,
+        sharedMemBytes: c_uint,
+        hStream: CUstream,
+        kernelParams: *mut *mut c_void,
+        extra: *mut *mut c_void,
+    ) -> CUresultCode {
+        let mut state = enter!(self, "cuLaunchKernel");
+        if !state.check_stream(hStream) {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        }
+        let Some(function) = state.functions.get(&(f as usize)) else {
+            return CUresult::CUDA_ERROR_INVALID_HANDLE.into();
+        };
+        let attribute = |attribute: CUFuncAttribute| {
+            function
+                .attributes
+                .get(&(attribute as c_int))
+                .copied()
+                .unwrap_or(0) as c_uint
+        };
+        if sharedMemBytes
+            > attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES)
+            || blockDimX * blockDimY * blockDimZ
+                > attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_THREADS_PER_BLOCK)
+        {
+            return CUresult::CUDA_ERROR_INVALID_VALUE.into();
+        }
+        let launch = FakeLaunch {
+            function: function.name.clone(),
+            grid: [gridDimX, gridDimY, gridDimZ],
+            block: [blockDimX, blockDimY, blockDimZ],
+            shared_memory: sharedMemBytes,
+            stream: hStream as usize,
+        };
+        state.launches.push(launch);
+        CUresult::CUDA_SUCCESS.into()
+    }
+}
@@ -0,0 +1,176 @@
+//! Automatic ordering of work on the same [`Buffer`] across streams.
+//!
+//! Every buffer remembers the stream that last wrote it and the streams that have read it
+//! since, each with an [`Event`] marking the end of that work. Before new work is queued on
+//! another stream, the stream waits for the events it conflicts with: reads wait for the last
+//! write, writes wait for the last write and all reads. One event is recorded per submission
+//! and shared by all buffers it accesses, e.g. the arguments of a kernel.
+//!
+//! Every device has its own legacy default stream. Work on it, i.e. every synchronous
+//! operation, is not marked right away. An event is only recorded on the default stream once another stream has to wait for
+//! it, so code that does not use streams pays nothing.
+//!
+//! [`Buffer`]: crate::cuda::Buffer
+
+use std::ptr::null;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tracing_mutex::parkinglot::DebugMutex;
+
+use crate::cuda::Device;
+use crate::cuda_api::*;
+use crate::cuda_result::*;
+use crate::event::Event;
+use crate::stream::Stream;
+
+static NEXT_STREAM_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A unique id for a [`Stream`], unlike its handle which the driver may reuse.
+pub(crate) fn next_stream_id() -> u64 {
+    NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Identifies a stream across devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamKey {
+    /// The legacy default stream of the device with the given id, shared by its users.
+    Default(i32),
+    /// A [`Stream`] by its [`next_stream_id`].
+    Created(u64),
+}
+
+/// The stream that work is queued on.
+#[derive(Clone, Copy)]
+pub(crate) struct Target {
+    key: StreamKey,
+    handle: CUstream,
+}
+
+impl Target {
+    /// Work on `stream`, or on the default stream of `device` if there is none.
+    pub(crate) fn new(device: &Device, stream: Option<&Stream>) -> Self {
+        match stream {
+            Some(stream) => Self {
+                key: StreamKey::Created(stream.id()),
+                handle: stream.as_raw(),
+            },
+            None => Self {
+                key: StreamKey::Default(device.id),
+                handle: null(),
+            },
+        }
+    }
+}
+
+/// Work queued on a stream that accessed one or more buffers.
+pub(crate) struct Submission {
+    stream: StreamKey,
+    /// The device whose context the work ran in.
+    device: Arc<Device>,
+    /// Completes with the work, recorded on demand for the default stream.
+    event: DebugMutex<Option<Arc<Event>>>,
+}
+
+impl Submission {
+    /// Marks the end of the work just queued on `target` on `device`.
+    pub(crate) fn record(device: &Arc<Device>, target: Target) -> Result<Arc<Self>> {
+        let event = match target.key {
+            StreamKey::Default(_) => None,
+            StreamKey::Created(_) => {
+                let event = Event::create(device, false)?;
+                event.record_raw(target.handle)?;
+                Some(Arc::new(event))
+            }
+        };
+        Ok(Arc::new(Self {
+            stream: target.key,
+            device: device.clone(),
+            event: DebugMutex::new(event),
+        }))
+    }
+
+    fn event(&self) -> Result<Arc<Event>> {
+        let mut event = self.event.lock();
+        if let Some(event) = &*event {
+            return Ok(event.clone());
+        }
+        let recorded = Arc::new(Event::create(&self.device, false)?);
+        recorded.record_raw(null())?;
+        *event = Some(recorded.clone());
+        Ok(recorded)
+    }
+}
+
+#[derive(Default)]
+struct HazardState {
+    writer: Option<Arc<Submission>>,
+    /// Reads since the last write, at most one per stream.
+    readers: Vec<Arc<Submission>>,
+}
+
+/// The accesses to a single buffer.
+#[derive(Default)]
+pub(crate) struct Hazards {
+    state: DebugMutex<HazardState>,
+}
+
+impl Hazards {
+    /// Makes `target` on `device` wait for the work on other streams that conflicts with a
+    /// read, or a write if `write` is set.
+    pub(crate) fn before(&self, device: &Arc<Device>, target: Target, write: bool) -> Result<()> {
+        let state = self.state.lock();
+        let HazardState { writer, readers } = &*state;
+        let readers = if write { &readers[..] } else { &[] };
+        // Reads on the stream of the write come after it, so waiting for them is enough.
+        let writer = writer
+            .as_ref()
+            .filter(|writer| !readers.iter().any(|read| read.stream == writer.stream));
+        for access in writer.into_iter().chain(readers) {
+            if access.stream == target.key {
+                continue;
+            }
+            let event = access.event()?;
+            // Work of an earlier generation has been destroyed by the reset.
+            if !event.is_valid() {
+                continue;
+            }
+            device
+                .call(|cuda| unsafe { cuda.cuStreamWaitEvent(target.handle, event.as_raw(), 0) })?;
+        }
+        Ok(())
+    }
+
+    /// Remembers that `submission` read the buffer, or wrote it if `write` is set.
+    pub(crate) fn after(&self, submission: &Arc<Submission>, write: bool) {
+        let mut state = self.state.lock();
+        if write {
+            state.writer = Some(submission.clone());
+            state.readers.clear();
+        } else {
+            state
+                .readers
+                .retain(|read| read.stream != submission.stream);
+            state.readers.push(submission.clone());
+        }
+    }
+}
+
+/// How a kernel argument accesses a buffer, see [`crate::module::KernelArg::access`].
+pub struct BufferAccess<'a> {
+    pub(crate) hazards: &'a Hazards,
+    pub(crate) write: bool,
+    pub(crate) device: &'a Arc<Device>,
+    /// The [`Device::generation`] the buffer was allocated in.
+    pub(crate) generation: u64,
+}
+
+impl BufferAccess<'_> {
+    /// Rejects buffers that a reset of their device has freed.
+    pub(crate) fn check_valid(&self) -> Result<()> {
+        if self.generation != self.device.generation() {
+            return Err(CUError::Invalidated(self.device.id));
+        }
+        Ok(())
+    }
+}
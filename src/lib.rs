@@ -0,0 +1,20 @@
+pub mod compile;
+pub mod cuda;
+#[allow(
+    unused,
+    non_snake_case,
+    non_camel_case_types,
+    clippy::too_many_arguments,
+    clippy::missing_safety_doc
+)]
+pub mod cuda_api;
+pub mod cuda_result;
+pub mod event;
+#[allow(non_snake_case, unused_variables)]
+pub mod fake;
+pub mod hazard;
+pub mod loader;
+pub mod module;
+pub mod properties;
+pub mod stream;
+pub mod trace;
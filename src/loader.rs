@@ -0,0 +1,221 @@
+use std::env;
+use std::ffi::{c_int, c_void, CString, OsString};
+use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
+
+use dlopen::raw::Library;
+use log::{trace, warn};
+
+use crate::cuda_api::*;
+use crate::cuda_result::*;
+
+/// Environment variable that points at the driver library (or the directory containing it).
+pub const DRIVER_PATH_ENV: &str = "CUDA_JIT_DRIVER_PATH";
+
+/// File names under which the driver library is installed.
+/// Most distributions only ship the versioned `libcuda.so.1` without the development symlink.
+const LIBRARY_NAMES: &[&str] = &["libcuda.so.1", "libcuda.so"];
+
+/// Directories in which distributions, container runtimes and WSL install the driver.
+const SEARCH_DIRS: &[&str] = &[
+    "/usr/lib64",
+    "/usr/lib/x86_64-linux-gnu",
+    "/usr/lib/aarch64-linux-gnu",
+    "/usr/lib/wsl/lib",
+    "/usr/local/nvidia/lib64",
+    "/usr/local/nvidia/lib",
+    "/usr/lib",
+];
+
+/// A path that was tried while looking for the driver and the reason it could not be loaded.
+#[derive(Debug, Clone)]
+pub struct LoadAttempt {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Expands `path` into library candidates: directories are joined with every known library name.
+fn expand(path: &Path, candidates: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        candidates.extend(LIBRARY_NAMES.iter().map(|name| path.join(name)));
+    } else {
+        candidates.push(path.to_owned());
+    }
+}
+
+/// Returns the paths that are tried, in order, when loading the driver.
+///
+/// The order is: `explicit`, `$CUDA_JIT_DRIVER_PATH`, every entry of `$LD_LIBRARY_PATH`,
+/// the usual distribution directories and finally the bare library names, which leaves the
+/// lookup to the dynamic linker.
+pub fn candidates(explicit: Option<&Path>) -> Vec<PathBuf> {
+    candidates_in(explicit, |name| env::var_os(name))
+}
+
+/// Like [`candidates`], but reads environment variables through `var`.
+pub fn candidates_in(
+    explicit: Option<&Path>,
+    var: impl Fn(&str) -> Option<OsString>,
+) -> Vec<PathBuf> {
+    let mut candidates = vec![];
+
+    if let Some(path) = explicit {
+        expand(path, &mut candidates);
+    }
+    if let Some(path) = var(DRIVER_PATH_ENV).filter(|path| !path.is_empty()) {
+        expand(Path::new(&path), &mut candidates);
+    }
+    if let Some(paths) = var("LD_LIBRARY_PATH") {
+        for dir in env::split_paths(&paths).filter(|dir| !dir.as_os_str().is_empty()) {
+            candidates.extend(LIBRARY_NAMES.iter().map(|name| dir.join(name)));
+        }
+    }
+    for dir in SEARCH_DIRS {
+        candidates.extend(LIBRARY_NAMES.iter().map(|name| Path::new(dir).join(name)));
+    }
+    candidates.extend(LIBRARY_NAMES.iter().map(PathBuf::from));
+
+    let mut unique = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if !unique.contains(&candidate) {
+            unique.push(candidate);
+        }
+    }
+    unique
+}
+
+/// Loads the first driver library out of [`candidates`] that can be opened.
+pub fn load(explicit: Option<&Path>) -> Result<(PathBuf, CudaApi)> {
+    load_from(candidates(explicit))
+}
+
+/// Loads the first of `candidates` that can be opened, or lists every attempt in
+/// [`CUError::DriverNotFound`].
+pub fn load_from(candidates: impl IntoIterator<Item = PathBuf>) -> Result<(PathBuf, CudaApi)> {
+    let mut attempts = vec![];
+    for path in candidates {
+        // Absolute paths that do not exist are skipped early to keep the error concise.
+        if path.is_absolute() && !path.exists() {
+            attempts.push(LoadAttempt {
+                path,
+                error: "not found".into(),
+            });
+            continue;
+        }
+        match unsafe { CudaApi::load(&path) } {
+            Ok(api) => {
+                trace!("Loaded CUDA driver from {}", path.display());
+                return Ok((path, api));
+            }
+            Err(err) => {
+                warn!("Could not load CUDA driver from {}: {err}", path.display());
+                attempts.push(LoadAttempt {
+                    path,
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+    Err(CUError::DriverNotFound(attempts))
+}
+
+/// Provides the exported symbols of a driver library.
+pub trait SymbolSource: Send + Sync {
+    /// Address of the exported symbol `name`.
+    fn lookup(&self, name: &str) -> Option<*const c_void>;
+}
+
+impl SymbolSource for Library {
+    fn lookup(&self, name: &str) -> Option<*const c_void> {
+        unsafe { self.symbol(name).ok() }
+    }
+}
+
+/// How an entry point of the driver was resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Through `cuGetProcAddress`, requesting the variant of the given API version.
+    ProcAddress { version: c_int },
+    /// Through the exported symbol of the given name.
+    Symbol(&'static str),
+}
+
+/// Picks the variant of every entry point that matches the declarations in [`crate::cuda_api`].
+///
+/// Drivers from 11.3 onwards are asked through `cuGetProcAddress`, which returns the variant
+/// belonging to a given API version. Older drivers, and entry points `cuGetProcAddress` does not
+/// know, fall back to the (versioned) exported symbol.
+pub(crate) struct Resolver<'a> {
+    source: &'a dyn SymbolSource,
+    get_proc_address: Option<PFN_cuGetProcAddress>,
+    version: c_int,
+}
+
+impl<'a> Resolver<'a> {
+    pub(crate) unsafe fn new(source: &'a dyn SymbolSource) -> Self {
+        let mut version = 0;
+        if let Some(ptr) = source.lookup("cuDriverGetVersion") {
+            let driver_get_version =
+                std::mem::transmute::<*const c_void, PFN_cuDriverGetVersion>(ptr);
+            if !driver_get_version(&mut version).is_success() {
+                version = 0;
+            }
+        }
+        // `cuGetProcAddress` itself was revised in 12.0, the unversioned symbol is the original.
+        let get_proc_address = source
+            .lookup("cuGetProcAddress")
+            .filter(|_| version > 0)
+            .map(|ptr| std::mem::transmute::<*const c_void, PFN_cuGetProcAddress>(ptr));
+        trace!(
+            "Resolving driver entry points for API version {}{}",
+            version.min(API_VERSION),
+            if get_proc_address.is_some() {
+                " through cuGetProcAddress"
+            } else {
+                ""
+            }
+        );
+        Self {
+            source,
+            get_proc_address,
+            version: version.min(API_VERSION),
+        }
+    }
+
+    /// Resolves the entry point `name`, which is exported as `symbol`.
+    /// Resolves the entry point `name`, exported as `symbol` or, by drivers that predate it,
+    /// as the ABI compatible `legacy`.
+    pub(crate) unsafe fn resolve(
+        &self,
+        name: &'static str,
+        symbol: &'static str,
+        legacy: Option<&'static str>,
+    ) -> Option<(*const c_void, Resolution)> {
+        if let Some(get_proc_address) = self.get_proc_address {
+            let cname = CString::new(name).unwrap();
+            let mut pfn = null_mut();
+            let result = get_proc_address(
+                cname.as_ptr(),
+                &mut pfn,
+                self.version,
+                CU_GET_PROC_ADDRESS_DEFAULT,
+            );
+            if result.is_success() && !pfn.is_null() {
+                return Some((
+                    pfn,
+                    Resolution::ProcAddress {
+                        version: self.version,
+                    },
+                ));
+            }
+        }
+        [Some(symbol), legacy]
+            .into_iter()
+            .flatten()
+            .find_map(|symbol| {
+                self.source
+                    .lookup(symbol)
+                    .map(|ptr| (ptr, Resolution::Symbol(symbol)))
+            })
+    }
+}
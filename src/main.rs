@@ -1,13 +1,6 @@
-mod cuda;
-#[allow(unused, non_snake_case, non_camel_case_types)]
-mod cuda_api;
-mod cuda_result;
-
 use std::sync::Arc;
 
-use dlopen::wrapper::Container;
-
-use self::cuda::{Device, CUDA};
+use cuda_jit::cuda::{Device, CUDA};
 
 fn main() {
     pretty_env_logger::init();
@@ -15,5 +8,5 @@ fn main() {
     let device = Arc::new(Device::create(&cuda, 0).unwrap());
 
     let mut buf = String::from("test");
-    cuda.compile_jit(&mut buf).unwrap();
+    device.compile_jit(&mut buf).unwrap();
 }
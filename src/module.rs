@@ -0,0 +1,390 @@
+//! Loaded modules and their kernel functions.
+
+use std::ffi::{c_void, CString};
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use log::trace;
+
+use crate::cuda::{Buffer, BufferSlice, BufferSliceMut, Device, Pod};
+use crate::cuda_api::*;
+use crate::cuda_result::*;
+use crate::hazard::{BufferAccess, Submission, Target};
+use crate::stream::Stream;
+
+/// A cubin or PTX image loaded into the context of a [`Device`].
+pub struct Module {
+    device: Arc<Device>,
+    module: CUmodule,
+    /// The [`Device::generation`] the module was loaded in.
+    generation: u64,
+}
+
+// SAFETY: Module handles can be used from any thread, the context is made current per call.
+unsafe impl Send for Module {}
+unsafe impl Sync for Module {}
+
+impl Module {
+    /// Loads a cubin, e.g. from [`Device::compile_jit`], or PTX source.
+    pub fn load(device: &Arc<Device>, image: &[u8]) -> Result<Self> {
+        // PTX has to be NUL terminated, cubins carry their size and ignore the extra byte.
+        let mut terminated;
+        let image = if image.last() == Some(&0) {
+            image
+        } else {
+            terminated = Vec::with_capacity(image.len() + 1);
+            terminated.extend_from_slice(image);
+            terminated.push(0);
+            &terminated
+        };
+        let mut module: CUmodule = null();
+        device.call(|cuda| unsafe {
+            cuda.cuModuleLoadData(&mut module, image.as_ptr() as *const c_void)
+        })?;
+        Ok(Self {
+            device: device.clone(),
+            module,
+            generation: device.generation(),
+        })
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    fn check_valid(&self) -> Result<()> {
+        if self.generation != self.device.generation() {
+            return Err(CUError::Invalidated(self.device.id));
+        }
+        Ok(())
+    }
+
+    /// Looks up the kernel `name`.
+    pub fn function(&self, name: &str) -> Result<Function<'_>> {
+        self.check_valid()?;
+        let Ok(cname) = CString::new(name) else {
+            return Err(CUError::FunctionNotFound(name.into()));
+        };
+        let mut function: CUfunction = null();
+        self.device.check_poisoned()?;
+        let _context = self.device.activate()?;
+        let result = unsafe {
+            self.device
+                .cuda
+                .cuModuleGetFunction(&mut function, self.module, cname.as_ptr())
+        };
+        if result.result() == Ok(CUresult::CUDA_ERROR_NOT_FOUND) {
+            return Err(CUError::FunctionNotFound(name.into()));
+        }
+        self.device.check(result)?;
+        let function = Function {
+            module: self,
+            function,
+            name: name.into(),
+            max_dynamic_shared_memory: AtomicUsize::new(0),
+        };
+        let max = function.max_dynamic_shared_memory()?;
+        function
+            .max_dynamic_shared_memory
+            .store(max, Ordering::Relaxed);
+        Ok(function)
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        // Modules of an earlier generation have been unloaded by the reset already.
+        if self.check_valid().is_ok() {
+            let _ = self
+                .device
+                .call(|cuda| unsafe { cuda.cuModuleUnload(self.module) });
+        }
+    }
+}
+
+/// A value that can be passed to a kernel.
+///
+/// # Safety
+///
+/// [`KernelArg::as_kernel_param`] has to point at a value with the layout of the corresponding
+/// kernel parameter, which stays alive for the duration of the launch.
+pub unsafe trait KernelArg {
+    fn as_kernel_param(&self) -> *mut c_void;
+
+    /// The buffer the argument refers to, so that launches on different streams are ordered,
+    /// see [`crate::hazard`].
+    fn access(&self) -> Option<BufferAccess<'_>> {
+        None
+    }
+}
+
+macro_rules! impl_kernel_arg {
+    ($($ty:ty),*) => {
+        $(
+            unsafe impl KernelArg for $ty {
+                fn as_kernel_param(&self) -> *mut c_void {
+                    self as *const Self as *mut c_void
+                }
+            }
+        )*
+    };
+}
+
+impl_kernel_arg!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+/// Passes the device address of the buffer, which the kernel may write to.
+unsafe impl<T: Pod> KernelArg for Buffer<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        self.device_ptr_ref() as *const *mut c_void as *mut c_void
+    }
+
+    fn access(&self) -> Option<BufferAccess<'_>> {
+        self.access(true)
+    }
+}
+
+/// Passes the device address of the view, which the kernel only reads. Kernels on different
+/// streams may read the same buffer concurrently.
+unsafe impl<T: Pod> KernelArg for BufferSlice<'_, T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        self.device_ptr_ref() as *const *mut c_void as *mut c_void
+    }
+
+    fn access(&self) -> Option<BufferAccess<'_>> {
+        self.buffer().access(false)
+    }
+}
+
+/// Passes the device address of the view, which the kernel may write to.
+unsafe impl<T: Pod> KernelArg for BufferSliceMut<'_, T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        self.device_ptr_ref() as *const *mut c_void as *mut c_void
+    }
+
+    fn access(&self) -> Option<BufferAccess<'_>> {
+        self.as_slice().buffer().access(true)
+    }
+}
+
+/// Grid and block dimensions of a launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaunchConfig {
+    pub grid: [u32; 3],
+    pub block: [u32; 3],
+    /// Dynamic shared memory per block in bytes.
+    pub shared_memory: u32,
+}
+
+impl LaunchConfig {
+    /// A one-dimensional launch of `grid` blocks of `block` threads.
+    pub fn linear(grid: u32, block: u32) -> Self {
+        Self {
+            grid: [grid, 1, 1],
+            block: [block, 1, 1],
+            shared_memory: 0,
+        }
+    }
+}
+
+/// A kernel of a [`Module`].
+pub struct Function<'a> {
+    module: &'a Module,
+    function: CUfunction,
+    name: String,
+    /// The [`Function::max_dynamic_shared_memory`] that launches are checked against.
+    max_dynamic_shared_memory: AtomicUsize,
+}
+
+// SAFETY: Function handles can be used from any thread, like the module they belong to.
+unsafe impl Send for Function<'_> {}
+unsafe impl Sync for Function<'_> {}
+
+impl Function<'_> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn attribute(&self, attribute: CUFuncAttribute) -> Result<i32> {
+        self.module.check_valid()?;
+        let mut value = 0;
+        self.module.device.call(|cuda| unsafe {
+            cuda.cuFuncGetAttribute(&mut value, attribute as i32, self.function)
+        })?;
+        Ok(value)
+    }
+
+    pub fn set_attribute(&self, attribute: CUFuncAttribute, value: i32) -> Result<()> {
+        self.module.check_valid()?;
+        self.module.device.call(|cuda| unsafe {
+            cuda.cuFuncSetAttribute(self.function, attribute as i32, value)
+        })?;
+        if attribute == CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES {
+            self.max_dynamic_shared_memory
+                .store(value as usize, Ordering::Relaxed);
+        }
+        trace!("Set {attribute:?} of {} to {value}", self.name);
+        Ok(())
+    }
+
+    pub fn num_registers(&self) -> Result<u32> {
+        Ok(self.attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_NUM_REGS)? as u32)
+    }
+
+    /// The most threads per block the function can be launched with.
+    pub fn max_threads_per_block(&self) -> Result<u32> {
+        Ok(self.attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_THREADS_PER_BLOCK)? as u32)
+    }
+
+    /// Statically allocated shared memory in bytes.
+    pub fn static_shared_memory(&self) -> Result<usize> {
+        Ok(self.attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_SHARED_SIZE_BYTES)? as usize)
+    }
+
+    /// Local memory per thread in bytes.
+    pub fn local_memory(&self) -> Result<usize> {
+        Ok(self.attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_LOCAL_SIZE_BYTES)? as usize)
+    }
+
+    /// The most dynamic shared memory in bytes the function may currently be launched with.
+    pub fn max_dynamic_shared_memory(&self) -> Result<usize> {
+        Ok(
+            self.attribute(CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES)?
+                as usize,
+        )
+    }
+
+    /// Dynamic shared memory in bytes that is available to the function on its device,
+    /// i.e. the opt-in limit per block minus the static shared memory.
+    pub fn available_dynamic_shared_memory(&self) -> Result<usize> {
+        let optin = self.module.device.properties.shared_memory_per_block_optin as usize;
+        Ok(optin.saturating_sub(self.static_shared_memory()?))
+    }
+
+    /// Returns [`CUError::SharedMemoryExceeded`] if `bytes` of dynamic shared memory exceed
+    /// [`Function::available_dynamic_shared_memory`].
+    pub fn check_dynamic_shared_memory(&self, bytes: usize) -> Result<()> {
+        let available = self.available_dynamic_shared_memory()?;
+        if bytes > available {
+            return Err(CUError::SharedMemoryExceeded {
+                function: self.name.clone(),
+                requested: bytes,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    /// Allows launches with up to `bytes` of dynamic shared memory, opting in to more than the
+    /// default 48 KiB if necessary.
+    pub fn set_max_dynamic_shared_memory(&self, bytes: usize) -> Result<()> {
+        self.check_dynamic_shared_memory(bytes)?;
+        self.set_attribute(
+            CUFuncAttribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES,
+            bytes as i32,
+        )
+    }
+
+    /// Sets the preferred share of the unified L1/shared memory used as shared memory, in
+    /// percent of the maximum, or `None` to leave it to the driver.
+    pub fn set_shared_memory_carveout(&self, percent: Option<u8>) -> Result<()> {
+        self.set_attribute(
+            CUFuncAttribute::CU_FUNC_ATTRIBUTE_PREFERRED_SHARED_MEMORY_CARVEOUT,
+            percent.map_or(-1, |percent| percent.min(100) as i32),
+        )
+    }
+
+    /// Sets the preferred split between L1 cache and shared memory on devices where it is fixed.
+    pub fn set_cache_config(&self, config: CUFuncCache) -> Result<()> {
+        self.module.check_valid()?;
+        self.module
+            .device
+            .call(|cuda| unsafe { cuda.cuFuncSetCacheConfig(self.function, config as i32) })
+    }
+
+    /// Launches the function on the legacy default stream.
+    ///
+    /// The dynamic shared memory has to fit [`Function::max_dynamic_shared_memory`], which
+    /// [`Function::set_max_dynamic_shared_memory`] raises.
+    ///
+    /// # Safety
+    ///
+    /// `args` have to match the parameters of the kernel, and the kernel must not access
+    /// memory out of bounds or race with other users of it.
+    pub unsafe fn launch(&self, config: LaunchConfig, args: &[&dyn KernelArg]) -> Result<()> {
+        self.launch_on(None, config, args)
+    }
+
+    /// Queues a launch on `stream`, see [`Function::launch`].
+    ///
+    /// # Safety
+    ///
+    /// As for [`Function::launch`], and the memory `args` point to has to stay alive until the
+    /// kernel has completed.
+    pub unsafe fn launch_async(
+        &self,
+        stream: &Stream,
+        config: LaunchConfig,
+        args: &[&dyn KernelArg],
+    ) -> Result<()> {
+        self.launch_on(Some(stream), config, args)
+    }
+
+    unsafe fn launch_on(
+        &self,
+        stream: Option<&Stream>,
+        config: LaunchConfig,
+        args: &[&dyn KernelArg],
+    ) -> Result<()> {
+        self.module.check_valid()?;
+        let device = &self.module.device;
+        let handle = match stream {
+            Some(stream) => stream.handle_for(device)?,
+            None => null(),
+        };
+        let target = Target::new(device, stream);
+        let shared_memory = config.shared_memory as usize;
+        let max = self.max_dynamic_shared_memory.load(Ordering::Relaxed);
+        if shared_memory > max {
+            // Report amounts the device cannot provide at all first.
+            self.check_dynamic_shared_memory(shared_memory)?;
+            return Err(CUError::SharedMemoryNotOptedIn {
+                function: self.name.clone(),
+                requested: shared_memory,
+                max,
+            });
+        }
+        let accesses: Vec<_> = args.iter().filter_map(|arg| arg.access()).collect();
+        for access in &accesses {
+            access.check_valid()?;
+        }
+        for access in &accesses {
+            access.hazards.before(device, target, access.write)?;
+        }
+        let mut params: Vec<*mut c_void> = args.iter().map(|arg| arg.as_kernel_param()).collect();
+        let [gx, gy, gz] = config.grid;
+        let [bx, by, bz] = config.block;
+        device.call(|cuda| {
+            cuda.cuLaunchKernel(
+                self.function,
+                gx,
+                gy,
+                gz,
+                bx,
+                by,
+                bz,
+                config.shared_memory,
+                handle,
+                params.as_mut_ptr(),
+                null_mut(),
+            )
+        })?;
+        if !accesses.is_empty() {
+            let submission = Submission::record(device, target)?;
+            for access in &accesses {
+                access.hazards.after(&submission, access.write);
+            }
+        }
+        Ok(())
+    }
+}
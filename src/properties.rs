@@ -0,0 +1,311 @@
+//! The full set of properties of a device, see [`crate::cuda::CUDA::device_properties`].
+
+use std::fmt::{self, Display};
+
+use derive_more::Deref;
+
+use crate::cuda::{DeviceInfo, CUDA};
+use crate::cuda_api::{CUAttribute, CUresult};
+use crate::cuda_result::Result;
+
+/// Properties of a device, in addition to its [`DeviceInfo`].
+///
+/// Attributes the driver does not know yet read as zero, so that newer properties are simply
+/// unavailable on older drivers.
+#[derive(Debug, Clone, PartialEq, Eq, Deref)]
+pub struct DeviceProperties {
+    #[deref]
+    pub info: DeviceInfo,
+    pub warp_size: u32,
+    pub max_threads_per_block: u32,
+    pub max_block_dim: [u32; 3],
+    pub max_grid_dim: [u32; 3],
+    pub max_threads_per_multiprocessor: u32,
+    pub max_blocks_per_multiprocessor: u32,
+    pub registers_per_block: u32,
+    pub registers_per_multiprocessor: u32,
+    /// Shared memory per block in bytes, without opting in.
+    pub shared_memory_per_block: u32,
+    /// Shared memory per block in bytes a kernel can opt in to.
+    pub shared_memory_per_block_optin: u32,
+    pub shared_memory_per_multiprocessor: u32,
+    pub total_constant_memory: u32,
+    /// Peak clock rate in kHz.
+    pub clock_rate: u32,
+    /// Peak memory clock rate in kHz.
+    pub memory_clock_rate: u32,
+    /// Width of the global memory bus in bits.
+    pub memory_bus_width: u32,
+    pub l2_cache_size: u32,
+    /// Number of copy engines that can run concurrently with kernels.
+    pub async_engine_count: u32,
+    /// The `CUcomputemode` of the device.
+    pub compute_mode: i32,
+    pub concurrent_kernels: bool,
+    pub kernel_exec_timeout: bool,
+    pub integrated: bool,
+    pub can_map_host_memory: bool,
+    pub ecc_enabled: bool,
+    pub tcc_driver: bool,
+    pub unified_addressing: bool,
+    pub managed_memory: bool,
+    pub concurrent_managed_access: bool,
+    pub pageable_memory_access: bool,
+    pub compute_preemption: bool,
+    pub cooperative_launch: bool,
+    pub cooperative_multi_device_launch: bool,
+    pub stream_priorities: bool,
+    pub memory_pools: bool,
+    pub cluster_launch: bool,
+}
+
+impl DeviceProperties {
+    pub(crate) fn query(cuda: &CUDA, info: DeviceInfo) -> Result<Self> {
+        let id = info.id;
+        let get = |attribute: CUAttribute| -> Result<u32> {
+            let mut value = 0;
+            let result = unsafe { cuda.cuDeviceGetAttribute(&mut value, attribute as i32, id) };
+            if result.result() == Ok(CUresult::CUDA_ERROR_INVALID_VALUE) {
+                return Ok(0);
+            }
+            result.check()?;
+            Ok(value as u32)
+        };
+        let flag = |attribute: CUAttribute| get(attribute).map(|value| value != 0);
+        use CUAttribute::*;
+        Ok(Self {
+            warp_size: get(CU_DEVICE_ATTRIBUTE_WARP_SIZE)?,
+            max_threads_per_block: get(CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_BLOCK)?,
+            max_block_dim: [
+                get(CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_X)?,
+                get(CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Y)?,
+                get(CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Z)?,
+            ],
+            max_grid_dim: [
+                get(CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_X)?,
+                get(CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Y)?,
+                get(CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Z)?,
+            ],
+            max_threads_per_multiprocessor: get(
+                CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_MULTIPROCESSOR,
+            )?,
+            max_blocks_per_multiprocessor: get(CU_DEVICE_ATTRIBUTE_MAX_BLOCKS_PER_MULTIPROCESSOR)?,
+            registers_per_block: get(CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_BLOCK)?,
+            registers_per_multiprocessor: get(
+                CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_MULTIPROCESSOR,
+            )?,
+            shared_memory_per_block: get(CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK)?,
+            shared_memory_per_block_optin: get(
+                CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN,
+            )?,
+            shared_memory_per_multiprocessor: get(
+                CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_MULTIPROCESSOR,
+            )?,
+            total_constant_memory: get(CU_DEVICE_ATTRIBUTE_TOTAL_CONSTANT_MEMORY)?,
+            clock_rate: get(CU_DEVICE_ATTRIBUTE_CLOCK_RATE)?,
+            memory_clock_rate: get(CU_DEVICE_ATTRIBUTE_MEMORY_CLOCK_RATE)?,
+            memory_bus_width: get(CU_DEVICE_ATTRIBUTE_GLOBAL_MEMORY_BUS_WIDTH)?,
+            l2_cache_size: get(CU_DEVICE_ATTRIBUTE_L2_CACHE_SIZE)?,
+            async_engine_count: get(CU_DEVICE_ATTRIBUTE_ASYNC_ENGINE_COUNT)?,
+            compute_mode: get(CU_DEVICE_ATTRIBUTE_COMPUTE_MODE)? as i32,
+            concurrent_kernels: flag(CU_DEVICE_ATTRIBUTE_CONCURRENT_KERNELS)?,
+            kernel_exec_timeout: flag(CU_DEVICE_ATTRIBUTE_KERNEL_EXEC_TIMEOUT)?,
+            integrated: flag(CU_DEVICE_ATTRIBUTE_INTEGRATED)?,
+            can_map_host_memory: flag(CU_DEVICE_ATTRIBUTE_CAN_MAP_HOST_MEMORY)?,
+            ecc_enabled: flag(CU_DEVICE_ATTRIBUTE_ECC_ENABLED)?,
+            tcc_driver: flag(CU_DEVICE_ATTRIBUTE_TCC_DRIVER)?,
+            unified_addressing: flag(CU_DEVICE_ATTRIBUTE_UNIFIED_ADDRESSING)?,
+            managed_memory: flag(CU_DEVICE_ATTRIBUTE_MANAGED_MEMORY)?,
+            concurrent_managed_access: flag(CU_DEVICE_ATTRIBUTE_CONCURRENT_MANAGED_ACCESS)?,
+            pageable_memory_access: flag(CU_DEVICE_ATTRIBUTE_PAGEABLE_MEMORY_ACCESS)?,
+            compute_preemption: flag(CU_DEVICE_ATTRIBUTE_COMPUTE_PREEMPTION_SUPPORTED)?,
+            cooperative_launch: flag(CU_DEVICE_ATTRIBUTE_COOPERATIVE_LAUNCH)?,
+            cooperative_multi_device_launch: flag(
+                CU_DEVICE_ATTRIBUTE_COOPERATIVE_MULTI_DEVICE_LAUNCH,
+            )?,
+            stream_priorities: flag(CU_DEVICE_ATTRIBUTE_STREAM_PRIORITIES_SUPPORTED)?,
+            memory_pools: flag(CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED)?,
+            cluster_launch: flag(CU_DEVICE_ATTRIBUTE_CLUSTER_LAUNCH)?,
+            info,
+        })
+    }
+
+    /// CUDA cores per multiprocessor, if the architecture is known.
+    pub fn cores_per_multiprocessor(&self) -> Option<u32> {
+        match (self.cc_major, self.cc_minor) {
+            (3, _) => Some(192),
+            (5, _) | (6, 1) | (6, 2) => Some(128),
+            (6, 0) | (7, _) | (8, 0) => Some(64),
+            (8, _) | (9, 0) => Some(128),
+            _ => None,
+        }
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "Yes"
+    } else {
+        "No"
+    }
+}
+
+/// Formats the properties like the `deviceQuery` sample of the CUDA toolkit.
+impl Display for DeviceProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        macro_rules! row {
+            ($label:expr, $($value:tt)*) => {
+                writeln!(f, "  {:<46} {}", concat!($label, ":"), format_args!($($value)*))?
+            };
+        }
+        writeln!(f, "Device {}: \"{}\"", self.id, self.name)?;
+        row!(
+            "CUDA Capability Major/Minor version number",
+            "{}.{}",
+            self.cc_major,
+            self.cc_minor
+        );
+        row!(
+            "Total amount of global memory",
+            "{:.0} MBytes ({} bytes)",
+            self.mem_total as f64 / 1048576.0,
+            self.mem_total
+        );
+        match self.cores_per_multiprocessor() {
+            Some(cores) => writeln!(
+                f,
+                "  {:<46} {} CUDA Cores",
+                format!(
+                    "({:03}) Multiprocessors, ({cores:03}) CUDA Cores/MP:",
+                    self.num_sm
+                ),
+                cores * self.num_sm as u32
+            )?,
+            None => row!("Multiprocessors", "{}", self.num_sm),
+        }
+        row!(
+            "GPU Max Clock rate",
+            "{:.0} MHz ({:.2} GHz)",
+            self.clock_rate as f64 * 1e-3,
+            self.clock_rate as f64 * 1e-6
+        );
+        row!(
+            "Memory Clock rate",
+            "{:.0} MHz",
+            self.memory_clock_rate as f64 * 1e-3
+        );
+        row!("Memory Bus Width", "{}-bit", self.memory_bus_width);
+        row!("L2 Cache Size", "{} bytes", self.l2_cache_size);
+        row!(
+            "Total amount of constant memory",
+            "{} bytes",
+            self.total_constant_memory
+        );
+        row!(
+            "Total amount of shared memory per block",
+            "{} bytes",
+            self.shared_memory_per_block
+        );
+        row!(
+            "Total shared memory per multiprocessor",
+            "{} bytes",
+            self.shared_memory_per_multiprocessor
+        );
+        row!(
+            "Total number of registers available per block",
+            "{}",
+            self.registers_per_block
+        );
+        row!("Warp size", "{}", self.warp_size);
+        row!(
+            "Maximum number of threads per multiprocessor",
+            "{}",
+            self.max_threads_per_multiprocessor
+        );
+        row!(
+            "Maximum number of threads per block",
+            "{}",
+            self.max_threads_per_block
+        );
+        let [x, y, z] = self.max_block_dim;
+        row!(
+            "Max dimension size of a thread block (x,y,z)",
+            "({x}, {y}, {z})"
+        );
+        let [x, y, z] = self.max_grid_dim;
+        row!(
+            "Max dimension size of a grid size    (x,y,z)",
+            "({x}, {y}, {z})"
+        );
+        row!(
+            "Concurrent copy and kernel execution",
+            "{} with {} copy engine(s)",
+            yes_no(self.async_engine_count > 0),
+            self.async_engine_count
+        );
+        row!(
+            "Run time limit on kernels",
+            "{}",
+            yes_no(self.kernel_exec_timeout)
+        );
+        row!(
+            "Integrated GPU sharing Host Memory",
+            "{}",
+            yes_no(self.integrated)
+        );
+        row!(
+            "Support host page-locked memory mapping",
+            "{}",
+            yes_no(self.can_map_host_memory)
+        );
+        row!(
+            "Device has ECC support",
+            "{}",
+            if self.ecc_enabled {
+                "Enabled"
+            } else {
+                "Disabled"
+            }
+        );
+        row!(
+            "Device supports Unified Addressing (UVA)",
+            "{}",
+            yes_no(self.unified_addressing)
+        );
+        row!(
+            "Device supports Managed Memory",
+            "{}",
+            yes_no(self.managed_memory)
+        );
+        row!(
+            "Device supports Compute Preemption",
+            "{}",
+            yes_no(self.compute_preemption)
+        );
+        row!(
+            "Supports Cooperative Kernel Launch",
+            "{}",
+            yes_no(self.cooperative_launch)
+        );
+        row!(
+            "Supports MultiDevice Co-op Kernel Launch",
+            "{}",
+            yes_no(self.cooperative_multi_device_launch)
+        );
+        row!(
+            "Device PCI Domain ID / Bus ID / location ID",
+            "{} / {} / {}",
+            self.pci_domain_id,
+            self.pci_bus_id,
+            self.pci_device_id
+        );
+        let compute_mode = match self.compute_mode {
+            0 => "Default (multiple host threads can use the device simultaneously)",
+            2 => "Prohibited (no host thread can use the device)",
+            3 => "Exclusive Process (many threads in one process can use the device)",
+            _ => "Unknown",
+        };
+        writeln!(f, "  Compute Mode:")?;
+        write!(f, "     < {compute_mode} >")
+    }
+}
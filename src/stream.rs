@@ -0,0 +1,326 @@
+//! Streams for ordering asynchronous work on a device.
+
+use std::any::Any;
+use std::ffi::{c_uint, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null;
+use std::sync::Arc;
+
+use log::{error, trace};
+use tracing_mutex::parkinglot::DebugMutex;
+
+use crate::cuda::Device;
+use crate::cuda_api::*;
+use crate::cuda_result::*;
+use crate::event::Event;
+use crate::hazard::next_stream_id;
+
+/// How a [`Stream`] orders its work relative to the legacy default stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamMode {
+    /// Work waits for, and is waited on by, work on the default stream.
+    #[default]
+    Blocking,
+    /// Work runs concurrently with work on the default stream.
+    NonBlocking,
+}
+
+impl StreamMode {
+    pub fn flags(self) -> c_uint {
+        match self {
+            StreamMode::Blocking => CU_STREAM_DEFAULT as c_uint,
+            StreamMode::NonBlocking => CU_STREAM_NON_BLOCKING as c_uint,
+        }
+    }
+
+    pub fn from_flags(flags: c_uint) -> Self {
+        if flags & CU_STREAM_NON_BLOCKING as c_uint != 0 {
+            StreamMode::NonBlocking
+        } else {
+            StreamMode::Blocking
+        }
+    }
+}
+
+/// Stream priorities supported by a device, see [`Device::stream_priority_range`].
+///
+/// Lower numbers mean higher priority, so `greatest` is less than or equal to `least`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamPriorityRange {
+    pub least: i32,
+    pub greatest: i32,
+}
+
+impl StreamPriorityRange {
+    /// Whether the device distinguishes priorities at all.
+    pub fn is_supported(&self) -> bool {
+        self.least != self.greatest
+    }
+
+    pub fn contains(&self, priority: i32) -> bool {
+        (self.greatest..=self.least).contains(&priority)
+    }
+}
+
+impl Device {
+    /// The range of priorities [`Stream::with_priority`] accepts on this device.
+    pub fn stream_priority_range(&self) -> Result<StreamPriorityRange> {
+        let (mut least, mut greatest) = (0, 0);
+        self.call(|cuda| unsafe { cuda.cuCtxGetStreamPriorityRange(&mut least, &mut greatest) })?;
+        Ok(StreamPriorityRange { least, greatest })
+    }
+}
+
+/// A queue of work on a [`Device`] that executes in order, concurrently to other streams.
+///
+/// The stream is destroyed when dropped. Work that is still pending completes regardless.
+pub struct Stream {
+    device: Arc<Device>,
+    stream: CUstream,
+    /// Identifies the stream in [`crate::hazard`].
+    id: u64,
+    mode: StreamMode,
+    /// The [`Device::generation`] the stream was created in.
+    generation: u64,
+}
+
+// SAFETY: Stream handles can be used from any thread, the context is made current per call.
+unsafe impl Send for Stream {}
+unsafe impl Sync for Stream {}
+
+impl Stream {
+    /// Creates a stream with the default priority.
+    pub fn create(device: &Arc<Device>, mode: StreamMode) -> Result<Self> {
+        let mut stream: CUstream = null();
+        device.call(|cuda| unsafe { cuda.cuStreamCreate(&mut stream, mode.flags()) })?;
+        Ok(Self::new(device, stream, mode))
+    }
+
+    /// Creates a stream with `priority`, which the driver clamps to
+    /// [`Device::stream_priority_range`].
+    pub fn with_priority(device: &Arc<Device>, mode: StreamMode, priority: i32) -> Result<Self> {
+        let mut stream: CUstream = null();
+        device.call(|cuda| unsafe {
+            cuda.cuStreamCreateWithPriority(&mut stream, mode.flags(), priority)
+        })?;
+        Ok(Self::new(device, stream, mode))
+    }
+
+    fn new(device: &Arc<Device>, stream: CUstream, mode: StreamMode) -> Self {
+        trace!("Created {mode:?} stream {stream:?} on device {}", device.id);
+        Self {
+            device: device.clone(),
+            stream,
+            id: next_stream_id(),
+            mode,
+            generation: device.generation(),
+        }
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    pub fn mode(&self) -> StreamMode {
+        self.mode
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The raw handle, e.g. to pass to other CUDA libraries.
+    pub fn as_raw(&self) -> CUstream {
+        self.stream
+    }
+
+    /// Returns the handle for work on `device`, which has to own the stream.
+    pub(crate) fn handle_for(&self, device: &Arc<Device>) -> Result<CUstream> {
+        if !Arc::ptr_eq(&self.device, device) {
+            return Err(CUError::StreamDeviceMismatch {
+                stream: self.device.id,
+                device: device.id,
+            });
+        }
+        self.check_valid()?;
+        Ok(self.stream)
+    }
+
+    fn check_valid(&self) -> Result<()> {
+        if self.generation != self.device.generation() {
+            return Err(CUError::Invalidated(self.device.id));
+        }
+        Ok(())
+    }
+
+    /// The priority in effect, after clamping by the driver.
+    pub fn priority(&self) -> Result<i32> {
+        self.check_valid()?;
+        let mut priority = 0;
+        self.device
+            .call(|cuda| unsafe { cuda.cuStreamGetPriority(self.stream, &mut priority) })?;
+        Ok(priority)
+    }
+
+    /// Makes work queued on this stream from now on wait until `event` has completed.
+    ///
+    /// The event may belong to another device. Waits for buffers used across streams are
+    /// inserted automatically, this is only needed for other dependencies.
+    pub fn wait_event(&self, event: &Event) -> Result<()> {
+        self.check_valid()?;
+        if !event.is_valid() {
+            return Err(CUError::Invalidated(event.device().id));
+        }
+        self.device
+            .call(|cuda| unsafe { cuda.cuStreamWaitEvent(self.stream, event.as_raw(), 0) })
+    }
+
+    /// Runs `callback` on a driver thread once the work queued on the stream so far has
+    /// completed. Later work on the stream waits for the callback to return.
+    ///
+    /// The callback must not call into CUDA. It runs even if the stream is dropped before, and
+    /// is dropped without running if [`Device::recover`] resets the context first. Panics are
+    /// caught and logged.
+    pub fn on_complete(&self, callback: impl FnOnce() + Send + 'static) -> Result<()> {
+        self.device.callbacks.queue(Box::new(callback), |data| {
+            self.check_valid()?;
+            self.device
+                .call(|cuda| unsafe { cuda.cuLaunchHostFunc(self.stream, run_callback, data) })
+        })
+    }
+
+    /// Waits until all work submitted to the stream has completed.
+    pub fn synchronize(&self) -> Result<()> {
+        self.check_valid()?;
+        self.device
+            .call(|cuda| unsafe { cuda.cuStreamSynchronize(self.stream) })
+    }
+
+    /// Returns whether all work submitted to the stream has completed, without blocking.
+    pub fn query(&self) -> Result<bool> {
+        self.check_valid()?;
+        self.device.check_poisoned()?;
+        let _context = self.device.activate()?;
+        let result = unsafe { self.device.cuda.cuStreamQuery(self.stream) };
+        if result.result() == Ok(CUresult::CUDA_ERROR_NOT_READY) {
+            return Ok(false);
+        }
+        self.device.check(result)?;
+        Ok(true)
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        // Streams of an earlier generation have been destroyed by the reset already.
+        if self.check_valid().is_ok() {
+            let _ = self
+                .device
+                .call(|cuda| unsafe { cuda.cuStreamDestroy(self.stream) });
+        }
+    }
+}
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// A callback queued with the driver, which holds a reference to the slot until the callback
+/// is claimed.
+struct CallbackSlot {
+    callback: DebugMutex<Option<Callback>>,
+}
+
+impl CallbackSlot {
+    /// Takes the callback along with the driver's reference to the slot.
+    ///
+    /// Only the first caller, either [`run_callback`] or [`PendingCallbacks::discard`],
+    /// succeeds, so the reference is released exactly once.
+    fn claim(&self) -> Option<Callback> {
+        self.callback.lock().take()
+    }
+
+    fn is_claimed(&self) -> bool {
+        self.callback.lock().is_none()
+    }
+}
+
+/// The trampoline for [`Stream::on_complete`], called by the driver with its reference to the
+/// [`CallbackSlot`].
+unsafe extern "C" fn run_callback(data: *mut c_void) {
+    let slot = data as *const CallbackSlot;
+    let Some(callback) = (*slot).claim() else {
+        return;
+    };
+    let _slot = Arc::from_raw(slot);
+    // Unwinding into the driver is undefined behavior.
+    if let Err(panic) = catch_unwind(AssertUnwindSafe(callback)) {
+        error!("Stream callback panicked: {}", panic_message(&*panic));
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => panic
+            .downcast_ref::<String>()
+            .map_or("unknown panic", String::as_str),
+    }
+}
+
+/// The callbacks of a [`Device`] that the driver may not have run yet.
+#[derive(Default)]
+pub(crate) struct PendingCallbacks {
+    slots: DebugMutex<Vec<Arc<CallbackSlot>>>,
+}
+
+impl PendingCallbacks {
+    /// Hands `callback` to the driver through `launch`.
+    ///
+    /// The lock is held throughout, so that [`PendingCallbacks::discard_after`] sees every
+    /// callback the driver has accepted.
+    fn queue(
+        &self,
+        callback: Callback,
+        launch: impl FnOnce(*mut c_void) -> Result<()>,
+    ) -> Result<()> {
+        let mut slots = self.slots.lock();
+        slots.retain(|slot| !slot.is_claimed());
+        let slot = Arc::new(CallbackSlot {
+            callback: DebugMutex::new(Some(callback)),
+        });
+        let data = Arc::into_raw(slot.clone()) as *mut c_void;
+        match launch(data) {
+            Ok(()) => {
+                slots.push(slot);
+                Ok(())
+            }
+            Err(err) => {
+                // SAFETY: The driver rejected the callback, so its reference is ours.
+                drop(unsafe { Arc::from_raw(data as *const CallbackSlot) });
+                Err(err)
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.slots.lock().iter().all(|slot| slot.is_claimed())
+    }
+
+    /// Runs `reset`, after which the driver no longer runs the pending callbacks, and drops
+    /// them. No callbacks are queued in between.
+    pub(crate) fn discard_after(&self, reset: impl FnOnce() -> Result<()>) -> Result<()> {
+        let mut slots = self.slots.lock();
+        reset()?;
+        for slot in slots.drain(..) {
+            if slot.claim().is_some() {
+                // SAFETY: The driver will not run the callback anymore, so its reference is ours.
+                unsafe { Arc::decrement_strong_count(Arc::as_ptr(&slot)) };
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the pending callbacks of a context that is unusable.
+    pub(crate) fn discard(&self) {
+        let _ = self.discard_after(|| Ok(()));
+    }
+}
@@ -0,0 +1,154 @@
+//! Opt-in recording of driver calls, see [`crate::cuda::CUDA::enable_trace`].
+//!
+//! Every call made through [`crate::cuda_api::Api`] is recorded with its arguments, result and
+//! host-side duration. The recording can be exported in the Chrome Trace Event format and opened
+//! in Perfetto or `chrome://tracing`.
+//!
+//! Setting [`TRACE_PATH_ENV`] traces every [`crate::cuda::CUDA`] instance from its creation and
+//! writes the recording to the given path once the instance is dropped.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use tracing_mutex::parkinglot::DebugMutex;
+
+use crate::cuda_api::CUresultCode;
+
+/// Environment variable naming the file that driver calls are traced to.
+pub const TRACE_PATH_ENV: &str = "CUDA_JIT_TRACE";
+
+/// A single recorded driver call.
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    pub function: &'static str,
+    /// Names and values of the arguments.
+    pub args: Vec<(&'static str, String)>,
+    pub result: CUresultCode,
+    /// Start of the call relative to the creation of the [`Trace`].
+    pub start: Duration,
+    pub duration: Duration,
+    /// Index of the calling thread, in order of their first call.
+    pub thread: usize,
+}
+
+impl TraceEvent {
+    /// Name of the result, or its raw code if it is unknown.
+    pub fn result_name(&self) -> String {
+        match self.result.get() {
+            Ok(result) => format!("{result:?}"),
+            Err(code) => code.to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TraceState {
+    events: Vec<TraceEvent>,
+    threads: HashMap<ThreadId, usize>,
+}
+
+/// An in-memory log of driver calls.
+pub struct Trace {
+    epoch: Instant,
+    state: DebugMutex<TraceState>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            state: DebugMutex::new(TraceState::default()),
+        }
+    }
+
+    pub(crate) fn record(
+        &self,
+        function: &'static str,
+        args: &[(&'static str, String)],
+        result: CUresultCode,
+        start: Instant,
+    ) {
+        let duration = start.elapsed();
+        let mut state = self.state.lock();
+        let threads = state.threads.len();
+        let thread = *state
+            .threads
+            .entry(thread::current().id())
+            .or_insert(threads);
+        state.events.push(TraceEvent {
+            function,
+            args: args.to_vec(),
+            result,
+            start: start.saturating_duration_since(self.epoch),
+            duration,
+            thread,
+        });
+    }
+
+    /// All calls recorded so far.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.state.lock().events.clone()
+    }
+
+    pub fn clear(&self) {
+        self.state.lock().events.clear();
+    }
+
+    /// Serializes the recorded calls as Chrome Trace Event JSON.
+    pub fn to_chrome_json(&self) -> String {
+        let state = self.state.lock();
+        let mut json = String::from("{\"traceEvents\":[");
+        for (i, event) in state.events.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"name\":{},\"cat\":\"cuda\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":{},\"args\":{{",
+                escape(event.function),
+                event.start.as_secs_f64() * 1e6,
+                event.duration.as_secs_f64() * 1e6,
+                event.thread,
+            )
+            .unwrap();
+            for (name, value) in &event.args {
+                write!(json, "{}:{},", escape(name), escape(value)).unwrap();
+            }
+            write!(json, "\"result\":{}}}}}", escape(&event.result_name())).unwrap();
+        }
+        json.push_str("]}");
+        json
+    }
+
+    /// Writes the recorded calls as Chrome Trace Event JSON to `path`.
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_chrome_json())
+    }
+}
+
+/// Quotes `s` as a JSON string.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
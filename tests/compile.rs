@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use cuda_jit::compile::{CompileReport, Diagnostic, Severity};
+use cuda_jit::cuda::{Device, CUDA};
+use cuda_jit::cuda_api::CUresult;
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::fake::FakeDriver;
+
+const INFO_LOG: &str = "\
+ptxas info    : 0 bytes gmem
+ptxas info    : Compiling entry function 'saxpy' for 'sm_86'
+ptxas info    : Function properties for saxpy
+    16 bytes stack frame, 8 bytes spill stores, 12 bytes spill loads
+ptxas info    : Used 255 registers, 1024 bytes smem, 368 bytes cmem[0]
+info    : Function properties for 'copy':
+info    : used 8 registers, 0 stack, 0 bytes smem, 368 bytes cmem[0], 0 bytes lmem
+info    : Compile time = 1.500 ms
+";
+
+const ERROR_LOG: &str = "\
+ptxas application ptx input, line 12; error   : Unknown symbol 'foo'
+ptxas application ptx input, line 20; warning : Unused variable 'bar'
+ptxas fatal   : Ptx assembly aborted due to errors
+";
+
+#[test]
+fn info_log_is_parsed_into_a_report() {
+    let report = CompileReport::parse(INFO_LOG);
+    assert_eq!(report.functions.len(), 2);
+
+    let saxpy = &report.functions[0];
+    assert_eq!(saxpy.name, "saxpy");
+    assert_eq!(saxpy.registers, 255);
+    assert_eq!(saxpy.stack_frame, 16);
+    assert_eq!((saxpy.spill_stores, saxpy.spill_loads), (8, 12));
+    assert_eq!(saxpy.shared_memory, 1024);
+
+    let copy = &report.functions[1];
+    assert_eq!(copy.name, "copy");
+    assert_eq!(copy.registers, 8);
+    assert!(!copy.spills());
+
+    assert!(report.spills());
+    assert_eq!(report.compile_time, Some(Duration::from_micros(1500)));
+}
+
+#[test]
+fn error_log_lines_become_diagnostics() {
+    assert_eq!(
+        Diagnostic::parse("ptxas application ptx input, line 12; error   : Unknown symbol 'foo'"),
+        Some(Diagnostic {
+            line: Some(12),
+            severity: Severity::Error,
+            message: "Unknown symbol 'foo'".into(),
+        })
+    );
+    assert_eq!(
+        Diagnostic::parse("ptxas fatal   : Ptx assembly aborted due to errors").map(|d| d.severity),
+        Some(Severity::Fatal)
+    );
+    assert_eq!(Diagnostic::parse("    16 bytes stack frame"), None);
+}
+
+#[test]
+fn compile_jit_reports_diagnostics_and_resource_usage() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Device::create(&cuda, 0).unwrap();
+
+    fake.set_jit_info_log(INFO_LOG);
+    let (cubin, report) = device.compile_jit(&mut String::from("ptx")).unwrap();
+    assert_eq!(cubin, b"ptx");
+    assert_eq!(report.functions[0].registers, 255);
+
+    fake.fail_jit(ERROR_LOG);
+    let Err(CUError::Compile(err)) = device.compile_jit(&mut String::from("ptx")) else {
+        panic!("compilation should have failed");
+    };
+    assert_eq!(err.error.result(), Some(CUresult::CUDA_ERROR_INVALID_PTX));
+    assert_eq!(err.diagnostics.len(), 3);
+    assert_eq!(err.errors().count(), 2);
+    assert_eq!(err.diagnostics[1].line, Some(20));
+    assert_eq!(fake.call_count("cuLinkDestroy"), 2);
+
+    // Diagnostics take precedence over a failure to clean up.
+    fake.fail_jit(ERROR_LOG);
+    fake.fail_next("cuLinkDestroy", CUresult::CUDA_ERROR_INVALID_HANDLE);
+    let Err(CUError::Compile(err)) = device.compile_jit(&mut String::from("ptx")) else {
+        panic!("compilation should have failed");
+    };
+    assert_eq!(err.diagnostics.len(), 3);
+
+    fake.fail_next("cuLinkDestroy", CUresult::CUDA_ERROR_INVALID_HANDLE);
+    let Err(err) = device.compile_jit(&mut String::from("ptx")) else {
+        panic!("the failed clean up should have been reported");
+    };
+    assert_eq!(err.result(), Some(CUresult::CUDA_ERROR_INVALID_HANDLE));
+}
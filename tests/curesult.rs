@@ -0,0 +1,57 @@
+use cuda_jit::cuda::CUDA;
+use cuda_jit::cuda_api::{CUresult, CUresultCode};
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::fake::FakeDriver;
+
+/// Number of variants of `CUresult`.
+const KNOWN_RESULTS: usize = 93;
+
+/// Every code the driver documents, plus surrounding and extreme values.
+fn codes() -> impl Iterator<Item = i32> {
+    (-16..=1100).chain([i32::MIN, i32::MAX, 0x7fff_0000])
+}
+
+#[test]
+fn conversion_roundtrips_every_known_code() {
+    let mut known = 0;
+    for code in codes() {
+        match CUresultCode(code).get() {
+            Ok(result) => {
+                assert_eq!(result as i32, code);
+                assert_eq!(CUresultCode::from(result), CUresultCode(code));
+                known += 1;
+            }
+            Err(unknown) => assert_eq!(unknown, code),
+        }
+    }
+    assert_eq!(known, KNOWN_RESULTS);
+}
+
+#[test]
+fn only_success_is_success() {
+    for code in codes() {
+        assert_eq!(CUresultCode(code).is_success(), code == 0);
+    }
+}
+
+#[test]
+fn checked_calls_map_every_code() {
+    let fake = FakeDriver::new();
+    let cuda = CUDA::with_driver(fake.clone()).unwrap();
+    for code in codes() {
+        fake.fail_next("cuCtxSynchronize", CUresultCode(code));
+        let result = unsafe { cuda.cuCtxSynchronize().check() };
+        match (CUresultCode(code).get(), result) {
+            (Ok(CUresult::CUDA_SUCCESS), Ok(())) => {}
+            (Ok(expected), Err(err @ CUError::CUResult(_))) => {
+                assert_eq!(err.result(), Some(expected))
+            }
+            (Err(_), Err(CUError::CUUnknownResult(error))) => {
+                assert_eq!((error.function, error.code), ("cuCtxSynchronize", code));
+                assert_eq!(error.location.file(), file!());
+                assert!(error.to_string().starts_with("cuCtxSynchronize failed at "));
+            }
+            (expected, result) => panic!("code {code}: expected {expected:?}, got {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use cuda_jit::cuda::{Buffer, Device, CUDA};
+use cuda_jit::cuda_api::CUAttribute;
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::fake::{FakeDevice, FakeDriver};
+
+fn fake_devices() -> FakeDriver {
+    let devices = [
+        ("Fake A100", 40 << 30, 0x17),
+        ("Fake T4", 16 << 30, 0x3b),
+        ("Fake A100", 80 << 30, 0x65),
+    ]
+    .into_iter()
+    .map(|(name, total_mem, bus)| {
+        let mut device = FakeDevice {
+            name: name.into(),
+            total_mem,
+            ..Default::default()
+        };
+        device
+            .attributes
+            .insert(CUAttribute::CU_DEVICE_ATTRIBUTE_PCI_BUS_ID as i32, bus);
+        device
+    });
+    FakeDriver::new().with_devices(devices)
+}
+
+#[test]
+fn devices_are_enumerated_without_contexts() {
+    let fake = fake_devices();
+    let cuda = CUDA::with_driver(fake.clone()).unwrap();
+    assert_eq!(cuda.driver_version(), (12, 0));
+
+    let devices = cuda.devices().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(devices.len(), 3);
+    assert_eq!(devices[1].name, "Fake T4");
+    assert_eq!(devices[1].pci_address(), "0000:3b:00.0");
+    assert_eq!(
+        devices[1].uuid_string(),
+        "GPU-fa4e0000-0000-0000-0000-000000000001"
+    );
+    assert_eq!(fake.call_count("cuDevicePrimaryCtxRetain"), 0);
+}
+
+#[test]
+fn devices_are_selected() {
+    let cuda = CUDA::with_driver(fake_devices()).unwrap();
+
+    assert_eq!(cuda.device_by_ordinal(2).unwrap().id, 2);
+    assert!(matches!(
+        cuda.device_by_ordinal(3),
+        Err(CUError::NoMatchingDevice(_))
+    ));
+    assert_eq!(cuda.device_by_pci_bus_id("3b:00.0").unwrap().id, 1);
+    assert_eq!(cuda.device_by_pci_bus_id("00000000:65:00.0").unwrap().id, 2);
+    assert!(cuda.device_by_pci_bus_id("0000:99:00.0").is_err());
+    assert_eq!(cuda.device_by_name("a100").unwrap().id, 0);
+    assert_eq!(cuda.device_with_most_memory().unwrap().id, 2);
+
+    let ids = |selector| {
+        cuda.select_devices(selector)
+            .unwrap()
+            .iter()
+            .map(|info| info.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids("2,0"), [2, 0]);
+    assert_eq!(ids("1, GPU-fa4e0000-0000-0000-0000-000000000002,1"), [1, 2]);
+    assert_eq!(ids("0,7,1"), [0]);
+    assert_eq!(ids("GPU-fa4e,0"), Vec::<i32>::new());
+    assert_eq!(ids(""), Vec::<i32>::new());
+}
+
+#[test]
+fn properties_cover_every_attribute() {
+    for value in 1..=134 {
+        assert_eq!(CUAttribute::try_from(value).unwrap() as i32, value);
+    }
+    assert!(CUAttribute::try_from(135).is_err());
+
+    let cuda = Arc::new(CUDA::with_driver(fake_devices()).unwrap());
+    let device = Device::create(&cuda, 1).unwrap();
+    let properties = &device.properties;
+    assert_eq!(properties.name, "Fake T4");
+    assert_eq!(properties.warp_size, 32);
+    assert_eq!(properties.max_block_dim, [1024, 1024, 64]);
+    assert_eq!(properties.shared_memory_per_block_optin, 101376);
+    assert!(properties.managed_memory && properties.cooperative_launch);
+    assert_eq!(properties.cores_per_multiprocessor(), Some(128));
+
+    let query = properties.to_string();
+    assert!(query.starts_with("Device 1: \"Fake T4\"\n"));
+    assert!(query.contains(
+        "  Total amount of global memory:                 16384 MBytes (17179869184 bytes)\n"
+    ));
+    assert!(query.contains("  (082) Multiprocessors, (128) CUDA Cores/MP:    10496 CUDA Cores\n"));
+    assert!(query.contains("  Max dimension size of a thread block (x,y,z):  (1024, 1024, 64)\n"));
+    assert!(query
+        .contains("  Concurrent copy and kernel execution:          Yes with 2 copy engine(s)\n"));
+}
+
+#[test]
+fn buffers_are_copied_between_peers() {
+    let fake = fake_devices();
+    fake.deny_peer_access(0, 2);
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    assert_eq!(
+        cuda.peer_access_matrix().unwrap(),
+        [[true, true, false], [true, true, true], [true, true, true]]
+    );
+
+    let devices: Vec<_> = (0..3)
+        .map(|id| Arc::new(Device::create(&cuda, id).unwrap()))
+        .collect();
+    devices[0].enable_peer_access(&devices[1]).unwrap();
+    devices[0].enable_peer_access(&devices[1]).unwrap();
+    assert_eq!(fake.call_count("cuCtxEnablePeerAccess"), 2);
+    assert!(matches!(
+        devices[0].enable_peer_access(&devices[2]),
+        Err(CUError::PeerAccessUnsupported(0, 2))
+    ));
+
+    let mut src = Buffer::create(&devices[0], 4).unwrap();
+    src.copy_from_slice(&[1, 2, 3, 4]).unwrap();
+    let mut dst = Buffer::create(&devices[1], 4).unwrap();
+    assert!(matches!(
+        dst.copy_from_buffer(&src),
+        Err(CUError::BufferDeviceMismatch { dst: 1, src: 0 })
+    ));
+    dst.copy_from_peer(&src).unwrap();
+    assert_eq!(fake.memory(dst.as_device_ptr()).unwrap(), [1, 2, 3, 4]);
+
+    let mut small = Buffer::create(&devices[2], 2).unwrap();
+    assert!(matches!(
+        small.copy_from_peer(&src),
+        Err(CUError::SizeMismatch { dst: 2, src: 4 })
+    ));
+}
@@ -0,0 +1,143 @@
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::Mutex;
+
+use cuda_jit::cuda_api::{CUresult, CUresultCode, CudaApi, Driver, API_VERSION};
+use cuda_jit::loader::{Resolution, SymbolSource};
+
+static QUERIES: Mutex<Vec<(String, c_int)>> = Mutex::new(vec![]);
+
+unsafe extern "C" fn driver_get_version_10020(version: *mut c_int) -> CUresultCode {
+    *version = 10020;
+    CUresult::CUDA_SUCCESS.into()
+}
+
+unsafe extern "C" fn driver_get_version_11020(version: *mut c_int) -> CUresultCode {
+    *version = 11020;
+    CUresult::CUDA_SUCCESS.into()
+}
+
+unsafe extern "C" fn driver_get_version_12040(version: *mut c_int) -> CUresultCode {
+    *version = 12040;
+    CUresult::CUDA_SUCCESS.into()
+}
+
+unsafe extern "C" fn get_proc_address(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    version: c_int,
+    _flags: u64,
+) -> CUresultCode {
+    let symbol = CStr::from_ptr(symbol).to_str().unwrap().to_owned();
+    QUERIES.lock().unwrap().push((symbol, version));
+    *pfn = stub as *mut c_void;
+    CUresult::CUDA_SUCCESS.into()
+}
+
+unsafe extern "C" fn stub() -> CUresultCode {
+    CUresult::CUDA_ERROR_NOT_SUPPORTED.into()
+}
+
+/// A library that exports every symbol it is asked for, except for `missing` ones.
+struct StubLibrary {
+    driver_get_version: *const c_void,
+    get_proc_address: Option<*const c_void>,
+    missing: &'static [&'static str],
+}
+
+unsafe impl Send for StubLibrary {}
+unsafe impl Sync for StubLibrary {}
+
+impl SymbolSource for StubLibrary {
+    fn lookup(&self, name: &str) -> Option<*const c_void> {
+        match name {
+            "cuDriverGetVersion" => Some(self.driver_get_version),
+            "cuGetProcAddress" => self.get_proc_address,
+            _ if self.missing.contains(&name) => None,
+            _ => Some(stub as *const c_void),
+        }
+    }
+}
+
+#[test]
+fn exported_symbols_use_versioned_names() {
+    let api = unsafe {
+        CudaApi::load_from(Box::new(StubLibrary {
+            driver_get_version: driver_get_version_11020 as *const c_void,
+            get_proc_address: None,
+            missing: &[],
+        }))
+        .unwrap()
+    };
+
+    for (name, symbol) in [
+        ("cuMemAlloc", "cuMemAlloc_v2"),
+        ("cuMemFree", "cuMemFree_v2"),
+        ("cuCtxPushCurrent", "cuCtxPushCurrent_v2"),
+        ("cuCtxPopCurrent", "cuCtxPopCurrent_v2"),
+        ("cuLinkCreate", "cuLinkCreate_v2"),
+        ("cuLinkAddData", "cuLinkAddData_v2"),
+        ("cuMemcpy2DAsync", "cuMemcpy2DAsync_v2"),
+        ("cuDeviceTotalMem", "cuDeviceTotalMem_v2"),
+        ("cuMemcpy", "cuMemcpy"),
+        ("cuLaunchKernel", "cuLaunchKernel"),
+        ("cuMemAllocAsync", "cuMemAllocAsync"),
+    ] {
+        assert_eq!(api.resolution(name), Some(&Resolution::Symbol(symbol)));
+    }
+    assert!(api.has_entry_point("cuMemAllocAsync"));
+}
+
+#[test]
+fn get_proc_address_is_queried_with_the_declared_api_version() {
+    let api = unsafe {
+        CudaApi::load_from(Box::new(StubLibrary {
+            driver_get_version: driver_get_version_12040 as *const c_void,
+            get_proc_address: Some(get_proc_address as *const c_void),
+            missing: &[],
+        }))
+        .unwrap()
+    };
+
+    let resolution = Resolution::ProcAddress {
+        version: API_VERSION,
+    };
+    assert_eq!(api.resolution("cuMemAlloc"), Some(&resolution));
+    assert_eq!(api.resolution("cuLinkCreate"), Some(&resolution));
+
+    // Entry points are requested by their base name, the driver picks the variant.
+    let queries = QUERIES.lock().unwrap();
+    assert!(queries.contains(&("cuMemAlloc".into(), API_VERSION)));
+    assert!(queries.contains(&("cuMemcpy2DAsync".into(), API_VERSION)));
+    assert!(!queries.iter().any(|(symbol, _)| symbol.ends_with("_v2")));
+}
+
+#[test]
+fn drivers_without_versioned_symbols_fall_back_to_legacy_names() {
+    const MISSING: &[&str] = &[
+        "cuDevicePrimaryCtxRelease_v2",
+        "cuDevicePrimaryCtxReset_v2",
+        "cuDevicePrimaryCtxSetFlags_v2",
+    ];
+    let api = unsafe {
+        CudaApi::load_from(Box::new(StubLibrary {
+            driver_get_version: driver_get_version_10020 as *const c_void,
+            get_proc_address: None,
+            missing: MISSING,
+        }))
+        .unwrap()
+    };
+    for symbol in MISSING {
+        let name = symbol.trim_end_matches("_v2");
+        assert_eq!(api.resolution(name), Some(&Resolution::Symbol(name)));
+    }
+
+    // Revisions with a different ABI have no fallback.
+    let result = unsafe {
+        CudaApi::load_from(Box::new(StubLibrary {
+            driver_get_version: driver_get_version_10020 as *const c_void,
+            get_proc_address: None,
+            missing: &["cuMemAlloc_v2"],
+        }))
+    };
+    assert!(result.is_err());
+}
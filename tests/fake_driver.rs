@@ -0,0 +1,415 @@
+use std::ops::Bound;
+use std::sync::Arc;
+
+use cuda_jit::cuda::{Buffer, Device, DeviceConfig, Scheduling, CUDA};
+use cuda_jit::cuda_api::{CUAttribute, CUlimit, CUresult};
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::fake::{FakeDevice, FakeDriver};
+
+#[test]
+fn device_reports_fake_attributes() {
+    let fake = FakeDriver::new();
+    fake.set_attribute(0, CUAttribute::CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT, 7);
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Device::create(&cuda, 0).unwrap();
+
+    assert_eq!(device.properties.num_sm, 7);
+    assert_eq!(
+        (device.properties.cc_major, device.properties.cc_minor),
+        (8, 6)
+    );
+    assert_eq!(fake.call_count("cuDevicePrimaryCtxRetain"), 1);
+
+    drop(device);
+    assert_eq!(fake.call_count("cuDevicePrimaryCtxRelease"), 1);
+}
+
+#[test]
+fn buffer_is_backed_by_host_memory() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+
+    let mut buffer = Buffer::create(&device, 4).unwrap();
+    buffer.copy_from_slice(&[1, 2, 3, 4]).unwrap();
+    assert_eq!(fake.allocation_count(), 1);
+
+    drop(buffer);
+    assert_eq!(fake.allocation_count(), 0);
+    assert_eq!(fake.call_count("cuMemFree"), 1);
+}
+
+#[test]
+fn typed_buffers_are_allocated_by_length() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+
+    let mut floats = Buffer::from_slice(&device, &[1.0f32, 2.0, 3.0]).unwrap();
+    assert_eq!((floats.len(), floats.size()), (3, 12));
+    let bytes = fake.memory(floats.as_device_ptr()).unwrap();
+    assert_eq!(bytes[4..8], 2.0f32.to_ne_bytes());
+    assert!(matches!(
+        floats.copy_from_slice(&[1.0; 4]),
+        Err(CUError::SizeMismatch { dst: 12, src: 16 })
+    ));
+
+    let mut zeroed = Buffer::<[u16; 2]>::zeroed(&device, 4).unwrap();
+    assert_eq!(fake.memory(zeroed.as_device_ptr()).unwrap(), [0; 16]);
+    assert_eq!(device.memory_stats().allocated, 28);
+
+    let empty = Buffer::<u64>::uninit(&device, 0).unwrap();
+    assert!(empty.is_empty() && empty.as_device_ptr().is_null());
+    assert_eq!(fake.call_count("cuMemAlloc"), 2);
+    drop(empty);
+    assert_eq!(fake.call_count("cuMemFree"), 0);
+
+    assert!(matches!(
+        Buffer::<u64>::uninit(&device, usize::MAX),
+        Err(CUError::AllocationOverflow { element: 8, .. })
+    ));
+    zeroed.copy_from_slice(&[[1, 2]; 4]).unwrap();
+}
+
+#[test]
+fn buffers_are_read_back_and_copied_in_ranges() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+
+    let mut buffer = Buffer::<u32>::zeroed(&device, 8).unwrap();
+    buffer.copy_from_slice_at(2, &[7, 8, 9]).unwrap();
+    assert_eq!(buffer.to_vec().unwrap(), [0, 0, 7, 8, 9, 0, 0, 0]);
+    let mut tail = [0; 4];
+    buffer.copy_to_slice_at(4, &mut tail).unwrap();
+    assert_eq!(tail, [9, 0, 0, 0]);
+
+    assert!(matches!(
+        buffer.copy_from_slice_at(6, &[1, 2, 3]),
+        Err(CUError::OutOfBounds {
+            offset: 24,
+            len: 12,
+            size: 32
+        })
+    ));
+    assert!(buffer.copy_to_slice_at(usize::MAX, &mut tail).is_err());
+    assert!(matches!(
+        buffer.copy_to_slice(&mut tail),
+        Err(CUError::SizeMismatch { dst: 32, src: 16 })
+    ));
+    let copies = fake.call_count("cuMemcpy");
+
+    let mut copy = Buffer::uninit(&device, 8).unwrap();
+    copy.copy_from_buffer(&buffer).unwrap();
+    assert_eq!(copy.to_vec().unwrap(), buffer.to_vec().unwrap());
+    assert_eq!(fake.call_count("cuMemcpy"), copies + 3);
+    assert!(Buffer::<u32>::uninit(&device, 0)
+        .unwrap()
+        .to_vec()
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn missing_devices_and_injected_failures_are_reported() {
+    let fake = FakeDriver::new().with_devices(Vec::<FakeDevice>::new());
+    assert!(matches!(CUDA::with_driver(fake), Err(CUError::NoDevice)));
+
+    let fake = FakeDriver::new();
+    fake.fail_next("cuInit", CUresult::CUDA_ERROR_NOT_INITIALIZED);
+    let Err(err) = CUDA::with_driver(fake) else {
+        panic!("cuInit should have failed");
+    };
+    assert_eq!(err.result(), Some(CUresult::CUDA_ERROR_NOT_INITIALIZED));
+    let message = err.to_string();
+    assert!(message.starts_with("cuInit failed at src/cuda.rs:"));
+    assert!(message.ends_with(": not initialized (CUDA_ERROR_NOT_INITIALIZED)"));
+}
+
+#[test]
+fn optional_entry_points_are_gated_by_driver_version() {
+    let cuda = CUDA::with_driver(FakeDriver::new()).unwrap();
+    assert!(cuda.capabilities().async_alloc);
+    cuda.require("cuMemAllocAsync").unwrap();
+
+    let cuda = CUDA::with_driver(FakeDriver::new().with_version(10020)).unwrap();
+    assert!(!cuda.capabilities().async_alloc);
+    let err = cuda.require("cuMemAllocAsync").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cuMemAllocAsync is not supported by driver 10.2 (requires 11.2)!"
+    );
+
+    let cuda = CUDA::with_driver(FakeDriver::new().without("cuMemFreeAsync")).unwrap();
+    assert!(!cuda.capabilities().async_alloc);
+}
+
+#[test]
+fn sticky_errors_poison_the_device_until_it_is_recovered() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let mut buffer = Buffer::create(&device, 4).unwrap();
+
+    // Recoverable errors leave the device usable.
+    fake.fail_next("cuMemcpy", CUresult::CUDA_ERROR_INVALID_VALUE);
+    assert!(buffer.copy_from_slice(&[1, 2, 3, 4]).is_err());
+    assert_eq!(device.poisoned(), None);
+
+    fake.fail_next("cuMemcpy", CUresult::CUDA_ERROR_ILLEGAL_ADDRESS);
+    let err = buffer.copy_from_slice(&[1, 2, 3, 4]).unwrap_err();
+    assert!(err.is_sticky());
+    assert_eq!(
+        device.poisoned(),
+        Some(CUresult::CUDA_ERROR_ILLEGAL_ADDRESS)
+    );
+    assert!(matches!(
+        Buffer::create(&device, 4),
+        Err(CUError::Poisoned(0, CUresult::CUDA_ERROR_ILLEGAL_ADDRESS))
+    ));
+
+    device.recover().unwrap();
+    assert_eq!(device.poisoned(), None);
+    assert!(!buffer.is_valid());
+    assert!(matches!(
+        buffer.copy_from_slice(&[1, 2, 3, 4]),
+        Err(CUError::Invalidated(0))
+    ));
+    Buffer::create(&device, 4).unwrap();
+
+    let frees = fake.call_count("cuMemFree");
+    drop(buffer);
+    assert_eq!(fake.call_count("cuMemFree"), frees);
+}
+
+#[test]
+fn calls_run_in_the_device_context() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let Err(err) = cuda.compile_jit(&mut String::from("ptx")) else {
+        panic!("compilation without a context should have failed");
+    };
+    assert_eq!(err.result(), Some(CUresult::CUDA_ERROR_INVALID_CONTEXT));
+
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let calls = fake.calls().len();
+    let buffer = Buffer::create(&device, 4).unwrap();
+    assert_eq!(
+        fake.calls()[calls..],
+        ["cuCtxPushCurrent", "cuMemAlloc", "cuCtxPopCurrent"]
+    );
+
+    let context = device.activate().unwrap();
+    drop(buffer);
+    drop(context);
+    assert_eq!(fake.call_count("cuCtxPushCurrent"), 3);
+    assert_eq!(fake.call_count("cuCtxPopCurrent"), 3);
+}
+
+#[test]
+fn devices_and_buffers_are_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CUDA>();
+    assert_send_sync::<Device>();
+    assert_send_sync::<Buffer>();
+
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let buffers: Vec<_> = (0..4u8)
+        .map(|i| {
+            let device = device.clone();
+            std::thread::spawn(move || {
+                let mut buffer = Buffer::create(&device, 4).unwrap();
+                buffer.copy_from_slice(&[i; 4]).unwrap();
+                buffer
+            })
+        })
+        .map(|handle| handle.join().unwrap())
+        .collect();
+
+    for (i, buffer) in buffers.iter().enumerate() {
+        assert_eq!(fake.memory(buffer.as_device_ptr()).unwrap(), [i as u8; 4]);
+    }
+    std::thread::spawn(move || drop(buffers)).join().unwrap();
+    assert_eq!(fake.allocation_count(), 0);
+}
+
+#[test]
+fn memory_is_accounted_and_capped() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+
+    let a = Buffer::create(&device, 1024).unwrap();
+    let b = Buffer::create(&device, 512).unwrap();
+    drop(a);
+    let stats = device.memory_stats();
+    assert_eq!(
+        (stats.allocated, stats.peak, stats.allocations),
+        (512, 1536, 1)
+    );
+    assert_eq!(device.memory_info().unwrap(), ((1 << 30) - 512, 1 << 30));
+
+    device.set_memory_limit(Some(1024));
+    let Err(err) = Buffer::create(&device, 1024) else {
+        panic!("allocation beyond the limit should have failed");
+    };
+    assert!(err.is_out_of_memory());
+    assert!(matches!(
+        err,
+        CUError::OutOfMemory {
+            device: 0,
+            requested: 1024,
+            allocated: 512,
+            limit: 1024
+        }
+    ));
+    assert_eq!(fake.call_count("cuMemAlloc"), 2);
+
+    fake.fail_next("cuMemAlloc", CUresult::CUDA_ERROR_OUT_OF_MEMORY);
+    assert!(Buffer::create(&device, 256).is_err_and(|err| err.is_out_of_memory()));
+    assert_eq!(device.memory_stats().allocated, 512);
+
+    device.reset_peak_memory();
+    fake.fail_next("cuMemAlloc", CUresult::CUDA_ERROR_OUT_OF_MEMORY);
+    assert!(Buffer::create(&device, 256).is_err());
+    assert_eq!(device.memory_stats().peak, 512);
+
+    // Sizes that overflow the accounting are rejected even without a limit.
+    device.set_memory_limit(None);
+    assert!(matches!(
+        Buffer::create(&device, usize::MAX),
+        Err(CUError::OutOfMemory {
+            allocated: 512,
+            limit: usize::MAX,
+            ..
+        })
+    ));
+
+    drop(b);
+    assert_eq!(device.memory_stats().peak, 512);
+    assert_eq!(device.memory_stats().allocated, 0);
+}
+
+#[test]
+fn context_limits_are_configured() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let config = DeviceConfig {
+        stack_size: Some(8192),
+        printf_fifo_size: Some(16 << 20),
+        ..Default::default()
+    };
+    let device = Device::create_with_config(&cuda, 0, &config).unwrap();
+    assert_eq!(fake.call_count("cuCtxSetLimit"), 2);
+    assert_eq!(device.limit(CUlimit::CU_LIMIT_STACK_SIZE).unwrap(), 8192);
+    assert_eq!(
+        device.limit(CUlimit::CU_LIMIT_PRINTF_FIFO_SIZE).unwrap(),
+        16 << 20
+    );
+    assert_eq!(
+        device.limit(CUlimit::CU_LIMIT_MALLOC_HEAP_SIZE).unwrap(),
+        8 << 20
+    );
+
+    device
+        .set_limit(CUlimit::CU_LIMIT_MALLOC_HEAP_SIZE, 64 << 20)
+        .unwrap();
+    assert_eq!(
+        device.limit(CUlimit::CU_LIMIT_MALLOC_HEAP_SIZE).unwrap(),
+        64 << 20
+    );
+
+    // A reset drops the limits of the context, recovering applies them again.
+    device.recover().unwrap();
+    assert_eq!(fake.call_count("cuCtxSetLimit"), 6);
+    assert_eq!(device.limit(CUlimit::CU_LIMIT_STACK_SIZE).unwrap(), 8192);
+    assert_eq!(
+        device.limit(CUlimit::CU_LIMIT_MALLOC_HEAP_SIZE).unwrap(),
+        64 << 20
+    );
+    drop(device);
+
+    let config = DeviceConfig {
+        max_l2_fetch_granularity: Some(256),
+        ..Default::default()
+    };
+    let Err(err) = Device::create_with_config(&cuda, 0, &config) else {
+        panic!("an invalid limit should have been rejected");
+    };
+    assert_eq!(err.result(), Some(CUresult::CUDA_ERROR_INVALID_VALUE));
+    assert_eq!(
+        fake.call_count("cuDevicePrimaryCtxRetain"),
+        fake.call_count("cuDevicePrimaryCtxRelease")
+    );
+}
+
+#[test]
+fn scheduling_is_set_before_retaining() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    assert!(!cuda.primary_context_state(0).unwrap().active);
+
+    let config = DeviceConfig {
+        scheduling: Some(Scheduling::BlockingSync),
+        ..Default::default()
+    };
+    let device = Device::create_with_config(&cuda, 0, &config).unwrap();
+    let calls = fake.calls();
+    let set_flags = calls
+        .iter()
+        .position(|c| *c == "cuDevicePrimaryCtxSetFlags");
+    let retain = calls.iter().position(|c| *c == "cuDevicePrimaryCtxRetain");
+    assert!(set_flags.unwrap() < retain.unwrap());
+
+    let state = device.primary_context_state().unwrap();
+    assert!(state.active);
+    assert_eq!(state.scheduling(), Scheduling::BlockingSync);
+    device.synchronize().unwrap();
+}
+
+#[test]
+fn slices_address_parts_of_a_buffer() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let mut buffer = Buffer::<u16>::zeroed(&device, 8).unwrap();
+
+    let mut middle = buffer.slice_mut(2..6).unwrap();
+    middle.fill(0xabcd).unwrap();
+    middle
+        .slice_mut(1..=1)
+        .unwrap()
+        .copy_from_slice(&[7])
+        .unwrap();
+    assert_eq!(middle.to_vec().unwrap(), [0xabcd, 7, 0xabcd, 0xabcd]);
+    assert_eq!(fake.call_count("cuMemsetD16"), 1);
+
+    let (mut head, tail) = buffer.split_at_mut(4).unwrap();
+    head.copy_from(&tail.as_slice()).unwrap();
+    assert_eq!(
+        buffer.to_vec().unwrap(),
+        [0xabcd, 0xabcd, 0, 0, 0xabcd, 0xabcd, 0, 0]
+    );
+
+    let view = buffer.slice(4..).unwrap();
+    assert_eq!((view.offset(), view.len(), view.size()), (4, 4, 8));
+    assert_eq!(
+        view.as_device_ptr(),
+        buffer.as_device_ptr().wrapping_byte_add(8)
+    );
+    assert_eq!(view.slice(..2).unwrap().to_vec().unwrap(), [0xabcd; 2]);
+    assert!(matches!(
+        view.slice(2..5),
+        Err(CUError::OutOfBounds {
+            offset: 4,
+            len: 6,
+            size: 8
+        })
+    ));
+    assert!(buffer
+        .slice((Bound::Excluded(6), Bound::Excluded(4)))
+        .is_err());
+    assert!(buffer.split_at_mut(9).is_err());
+}
@@ -0,0 +1,76 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::loader::{candidates_in, load_from, DRIVER_PATH_ENV};
+
+/// A fresh directory for the files of `test`.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cuda-jit-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn libraries(dir: &str) -> [PathBuf; 2] {
+    [
+        Path::new(dir).join("libcuda.so.1"),
+        Path::new(dir).join("libcuda.so"),
+    ]
+}
+
+#[test]
+fn candidates_follow_the_search_order() {
+    let dir = temp_dir("candidates");
+    let driver = dir.join("libcuda-custom.so");
+    fs::write(&driver, b"").unwrap();
+    let env = |name: &str| match name {
+        DRIVER_PATH_ENV => Some(OsString::from(&driver)),
+        "LD_LIBRARY_PATH" => Some("/opt/cuda/lib::/usr/lib64".into()),
+        _ => None,
+    };
+
+    // Directories are searched for the library names, files are taken as they are.
+    let candidates = candidates_in(Some(&dir), env);
+    let mut expected = libraries(dir.to_str().unwrap()).to_vec();
+    expected.push(driver.clone());
+    expected.extend(libraries("/opt/cuda/lib"));
+    expected.extend(libraries("/usr/lib64"));
+    assert_eq!(candidates[..expected.len()], expected);
+    // System directories follow without repeating earlier entries, the bare names come last.
+    let rest = &candidates[expected.len()..];
+    assert!(rest.iter().all(|path| !expected.contains(path)));
+    assert!(rest.contains(&Path::new("/usr/lib/wsl/lib/libcuda.so.1").to_owned()));
+    assert_eq!(rest[rest.len() - 2..], libraries(""));
+
+    let candidates = candidates_in(Some(&driver), |_| None);
+    assert_eq!(candidates[0], driver);
+    assert!(!candidates.contains(&driver.join("libcuda.so.1")));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failed_loads_list_every_attempt() {
+    let dir = temp_dir("attempts");
+    let invalid = dir.join("libcuda.so.1");
+    fs::write(&invalid, b"not a library").unwrap();
+    let missing = dir.join("missing").join("libcuda.so.1");
+    let unknown = PathBuf::from("libcuda-jit-does-not-exist.so");
+
+    let Err(CUError::DriverNotFound(attempts)) =
+        load_from([missing.clone(), invalid.clone(), unknown.clone()])
+    else {
+        panic!("loading invalid libraries should have failed");
+    };
+    let paths: Vec<_> = attempts.iter().map(|attempt| &attempt.path).collect();
+    assert_eq!(paths, [&missing, &invalid, &unknown]);
+    assert_eq!(attempts[0].error, "not found");
+
+    let message = CUError::DriverNotFound(attempts).to_string();
+    assert!(message.starts_with("Could not load the CUDA driver, tried: "));
+    for path in [&missing, &invalid, &unknown] {
+        assert!(message.contains(&path.display().to_string()));
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use cuda_jit::cuda::{Buffer, Device, CUDA};
+use cuda_jit::cuda_api::{CUFuncAttribute, CUFuncCache};
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::fake::{FakeDriver, FakeLaunch};
+use cuda_jit::module::{KernelArg, LaunchConfig, Module};
+
+const PTX: &[u8] = b".visible .entry scale(.param .u64 data, .param .f32 factor) {}";
+
+#[test]
+fn functions_are_looked_up_with_their_attributes() {
+    let fake = FakeDriver::new();
+    fake.set_kernel_attribute("scale", CUFuncAttribute::CU_FUNC_ATTRIBUTE_NUM_REGS, 40);
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let module = Module::load(&device, PTX).unwrap();
+
+    let scale = module.function("scale").unwrap();
+    assert_eq!(scale.name(), "scale");
+    assert_eq!(scale.num_registers().unwrap(), 40);
+    assert_eq!(scale.max_threads_per_block().unwrap(), 1024);
+    assert_eq!(scale.max_dynamic_shared_memory().unwrap(), 48 << 10);
+    scale
+        .set_cache_config(CUFuncCache::CU_FUNC_CACHE_PREFER_SHARED)
+        .unwrap();
+    scale.set_shared_memory_carveout(Some(50)).unwrap();
+
+    assert!(matches!(
+        module.function("missing"),
+        Err(CUError::FunctionNotFound(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn dynamic_shared_memory_is_checked_before_launch() {
+    let fake = FakeDriver::new();
+    fake.set_kernel_attribute(
+        "scale",
+        CUFuncAttribute::CU_FUNC_ATTRIBUTE_SHARED_SIZE_BYTES,
+        1024,
+    );
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let module = Module::load(&device, PTX).unwrap();
+    let scale = module.function("scale").unwrap();
+
+    let available = device.properties.shared_memory_per_block_optin as usize - 1024;
+    assert_eq!(scale.available_dynamic_shared_memory().unwrap(), available);
+    assert!(matches!(
+        scale.set_max_dynamic_shared_memory(available + 1),
+        Err(CUError::SharedMemoryExceeded { requested, .. }) if requested == available + 1
+    ));
+    assert_eq!(fake.call_count("cuFuncSetAttribute"), 0);
+
+    let buffer = Buffer::create(&device, 256).unwrap();
+    let config = LaunchConfig {
+        shared_memory: 64 << 10,
+        ..LaunchConfig::linear(4, 64)
+    };
+    assert!(matches!(
+        unsafe { scale.launch(config, &[&buffer, &2.0f32]) },
+        Err(CUError::SharedMemoryNotOptedIn { max, .. }) if max == 48 << 10
+    ));
+    assert!(fake.launches().is_empty());
+
+    scale.set_max_dynamic_shared_memory(64 << 10).unwrap();
+    let queries = fake.call_count("cuFuncGetAttribute");
+    unsafe { scale.launch(config, &[&buffer, &2.0f32]) }.unwrap();
+    unsafe { scale.launch(config, &[&buffer.slice(32..).unwrap(), &2.0f32]) }.unwrap();
+    assert_eq!(fake.call_count("cuFuncSetAttribute"), 1);
+    assert_eq!(fake.call_count("cuFuncGetAttribute"), queries);
+    assert_eq!(scale.max_dynamic_shared_memory().unwrap(), 64 << 10);
+    let launch = FakeLaunch {
+        function: "scale".into(),
+        grid: [4, 1, 1],
+        block: [64, 1, 1],
+        shared_memory: 64 << 10,
+        stream: 0,
+    };
+    assert_eq!(fake.launches(), [launch.clone(), launch]);
+}
+
+#[test]
+fn launches_reject_buffers_freed_by_a_reset() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let stale = Buffer::<f32>::uninit(&device, 64).unwrap();
+    device.recover().unwrap();
+
+    let module = Module::load(&device, PTX).unwrap();
+    let scale = module.function("scale").unwrap();
+    let config = LaunchConfig::linear(1, 64);
+    for args in [
+        &[&stale as &dyn KernelArg, &2.0f32],
+        &[&stale.slice(..).unwrap(), &2.0f32],
+    ] {
+        assert!(matches!(
+            unsafe { scale.launch(config, args) },
+            Err(CUError::Invalidated(0))
+        ));
+    }
+    assert!(fake.launches().is_empty());
+
+    let buffer = Buffer::<f32>::uninit(&device, 64).unwrap();
+    unsafe { scale.launch(config, &[&buffer, &2.0f32]) }.unwrap();
+}
@@ -0,0 +1,252 @@
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use cuda_jit::cuda::{Buffer, Device, CUDA};
+use cuda_jit::cuda_api::CUresult;
+use cuda_jit::cuda_result::CUError;
+use cuda_jit::event::{Event, GpuTimer};
+use cuda_jit::fake::{FakeDevice, FakeDriver};
+use cuda_jit::module::{KernelArg, LaunchConfig, Module};
+use cuda_jit::stream::{Stream, StreamMode, StreamPriorityRange};
+
+#[test]
+fn streams_are_created_with_priorities() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+
+    let range = device.stream_priority_range().unwrap();
+    assert_eq!(
+        range,
+        StreamPriorityRange {
+            least: 0,
+            greatest: -5
+        }
+    );
+    assert!(range.is_supported() && range.contains(-3));
+
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+    assert_eq!(stream.mode(), StreamMode::NonBlocking);
+    assert_eq!(stream.priority().unwrap(), 0);
+    let urgent = Stream::with_priority(&device, StreamMode::Blocking, -100).unwrap();
+    assert_eq!(urgent.priority().unwrap(), -5);
+    assert_eq!(fake.stream_count(), 2);
+
+    urgent.synchronize().unwrap();
+    assert!(urgent.query().unwrap());
+    fake.fail_next("cuStreamQuery", CUresult::CUDA_ERROR_NOT_READY);
+    assert!(!urgent.query().unwrap());
+
+    drop((stream, urgent));
+    assert_eq!(fake.stream_count(), 0);
+}
+
+#[test]
+fn async_work_is_queued_on_the_stream() {
+    let fake = FakeDriver::new().with_devices([FakeDevice::default(), FakeDevice::default()]);
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+
+    let mut a = Buffer::<u32>::uninit(&device, 4).unwrap();
+    a.fill_async(3, &stream).unwrap();
+    let mut b = Buffer::zeroed(&device, 4).unwrap();
+    b.slice_mut(1..3)
+        .unwrap()
+        .copy_from_slice_async(&[1, 2], &stream)
+        .unwrap();
+    a.slice_mut(..2)
+        .unwrap()
+        .copy_from_async(&b.slice(1..3).unwrap(), &stream)
+        .unwrap();
+    let mut result = [0; 4];
+    a.copy_to_slice_async(&mut result, &stream).unwrap();
+    stream.synchronize().unwrap();
+    assert_eq!(result, [1, 2, 3, 3]);
+    assert_eq!(fake.call_count("cuMemsetD32Async"), 1);
+    assert_eq!(fake.call_count("cuMemcpyAsync"), 3);
+
+    let module = Module::load(&device, b"kernel").unwrap();
+    let kernel = module.function("kernel").unwrap();
+    unsafe { kernel.launch_async(&stream, LaunchConfig::linear(1, 32), &[&a]) }.unwrap();
+    assert_eq!(fake.launches()[0].stream, stream.as_raw() as usize);
+
+    let other = Arc::new(Device::create(&cuda, 1).unwrap());
+    let other_stream = Stream::create(&other, StreamMode::Blocking).unwrap();
+    assert!(matches!(
+        a.fill_async(0, &other_stream),
+        Err(CUError::StreamDeviceMismatch {
+            stream: 1,
+            device: 0
+        })
+    ));
+}
+
+#[test]
+fn events_time_work_on_a_stream() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+
+    let ((), elapsed) = GpuTimer::measure(&stream, |_| {
+        fake.advance_clock(Duration::from_micros(1500));
+        Ok(())
+    })
+    .unwrap();
+    assert!(elapsed.abs_diff(Duration::from_micros(1500)) < Duration::from_micros(1));
+    assert_eq!(fake.call_count("cuEventRecord"), 2);
+
+    let start = Event::create(&device, false).unwrap();
+    let end = Event::create(&device, false).unwrap();
+    start.record(&stream).unwrap();
+    end.record(&stream).unwrap();
+    end.synchronize().unwrap();
+    assert!(end.query().unwrap());
+    assert!(end.elapsed_since(&start).is_err());
+
+    drop((start, end));
+    assert_eq!(fake.call_count("cuEventDestroy"), 4);
+}
+
+#[test]
+fn buffers_used_across_streams_are_ordered() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let (a, b) = (
+        Stream::create(&device, StreamMode::NonBlocking).unwrap(),
+        Stream::create(&device, StreamMode::NonBlocking).unwrap(),
+    );
+    let waits = || fake.call_count("cuStreamWaitEvent");
+
+    // Synchronous work alone records no events.
+    let mut buffer = Buffer::<u32>::zeroed(&device, 4).unwrap();
+    buffer.to_vec().unwrap();
+    assert_eq!((waits(), fake.call_count("cuEventCreate")), (0, 0));
+
+    // The first write on a stream waits for the default stream, reads on the same stream don't.
+    let mut result = [0; 4];
+    buffer.fill_async(1, &a).unwrap();
+    assert_eq!(waits(), 1);
+    buffer.copy_to_slice_async(&mut result, &a).unwrap();
+    assert_eq!(waits(), 1);
+
+    // Reads on different streams wait for the write, but not for each other.
+    let module = Module::load(&device, b"kernel").unwrap();
+    let kernel = module.function("kernel").unwrap();
+    let input = buffer.slice(..).unwrap();
+    unsafe { kernel.launch_async(&b, LaunchConfig::linear(1, 4), &[&input]) }.unwrap();
+    assert_eq!(waits(), 2);
+    buffer.copy_to_slice(&mut result).unwrap();
+    assert_eq!(waits(), 3);
+
+    // A write waits for every read on other streams.
+    buffer.fill_async(2, &b).unwrap();
+    assert_eq!(waits(), 5);
+
+    let event = Event::create(&device, false).unwrap();
+    event.record(&a).unwrap();
+    b.wait_event(&event).unwrap();
+    assert_eq!(waits(), 6);
+}
+
+#[test]
+fn callbacks_run_once_work_completes() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    stream.on_complete(move || sender.send(1).unwrap()).unwrap();
+    assert!(!stream.query().unwrap());
+    stream.synchronize().unwrap();
+    assert_eq!(receiver.try_recv(), Ok(1));
+
+    // Panics are caught and pending callbacks outlive the stream.
+    let counter = Arc::new(());
+    let held = counter.clone();
+    stream.on_complete(|| panic!("callback failed")).unwrap();
+    stream.on_complete(move || drop(held)).unwrap();
+    drop(stream);
+    assert_eq!(Arc::strong_count(&counter), 1);
+
+    // Callbacks that are not queued or discarded by a reset are dropped.
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+    let held = counter.clone();
+    fake.fail_next("cuLaunchHostFunc", CUresult::CUDA_ERROR_INVALID_VALUE);
+    assert!(stream.on_complete(move || drop(held)).is_err());
+    assert_eq!(Arc::strong_count(&counter), 1);
+    let held = counter.clone();
+    stream.on_complete(move || drop(held)).unwrap();
+    assert_eq!(Arc::strong_count(&counter), 2);
+    device.recover().unwrap();
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[test]
+fn callbacks_are_freed_with_a_poisoned_device() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+
+    let counter = Arc::new(());
+    let held = counter.clone();
+    stream.on_complete(move || drop(held)).unwrap();
+    fake.fail_next("cuStreamQuery", CUresult::CUDA_ERROR_ILLEGAL_ADDRESS);
+    assert!(stream.query().is_err());
+    assert!(device.poisoned().is_some());
+
+    drop((stream, device));
+    assert_eq!(Arc::strong_count(&counter), 1);
+    assert_eq!(fake.call_count("cuCtxSynchronize"), 0);
+}
+
+#[test]
+fn launches_record_one_event_for_all_buffers() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let stream = Stream::create(&device, StreamMode::NonBlocking).unwrap();
+    let module = Module::load(&device, b"kernel").unwrap();
+    let kernel = module.function("kernel").unwrap();
+
+    let input = Buffer::<f32>::uninit(&device, 32).unwrap();
+    let (mut a, mut b) = (
+        Buffer::<f32>::uninit(&device, 32).unwrap(),
+        Buffer::<f32>::uninit(&device, 32).unwrap(),
+    );
+    for _ in 0..10 {
+        let args = [
+            &input.slice(..).unwrap() as &dyn KernelArg,
+            &a.slice_mut(..).unwrap(),
+            &b.slice_mut(..).unwrap(),
+        ];
+        unsafe { kernel.launch_async(&stream, LaunchConfig::linear(32, 32), &args) }.unwrap();
+    }
+    assert_eq!(fake.call_count("cuEventCreate"), 10);
+    assert_eq!(fake.event_count(), 1);
+}
+
+#[test]
+fn default_streams_of_different_devices_are_ordered() {
+    let fake = FakeDriver::new().with_devices([FakeDevice::default(), FakeDevice::default()]);
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let a = Arc::new(Device::create(&cuda, 0).unwrap());
+    let b = Arc::new(Device::create(&cuda, 1).unwrap());
+    a.enable_peer_access(&b).unwrap();
+    let waits = || fake.call_count("cuStreamWaitEvent");
+
+    let mut src = Buffer::<u32>::zeroed(&a, 4).unwrap();
+    let mut dst = Buffer::<u32>::uninit(&b, 4).unwrap();
+    // The default stream of `b` waits for the write on the default stream of `a`.
+    dst.copy_from_peer(&src).unwrap();
+    assert_eq!(waits(), 1);
+    dst.fill(1).unwrap();
+    assert_eq!(waits(), 1);
+    // And `a` waits for the read on `b` before overwriting the buffer.
+    src.copy_from_slice(&[1, 2, 3, 4]).unwrap();
+    assert_eq!(waits(), 2);
+}
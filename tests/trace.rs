@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use cuda_jit::cuda::{Buffer, Device, CUDA};
+use cuda_jit::cuda_api::CUresult;
+use cuda_jit::fake::FakeDriver;
+
+#[test]
+fn driver_calls_are_traced_once_enabled() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    let trace = cuda.enable_trace();
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+
+    let buffer = Buffer::create(&device, 64).unwrap();
+    fake.fail_next("cuMemAlloc", CUresult::CUDA_ERROR_OUT_OF_MEMORY);
+    assert!(Buffer::create(&device, 128).is_err());
+    drop(buffer);
+
+    let events = trace.events();
+    assert!(events.iter().all(|e| e.function != "cuInit"));
+    let allocs: Vec<_> = events
+        .iter()
+        .filter(|e| e.function == "cuMemAlloc")
+        .collect();
+    assert_eq!(allocs.len(), 2);
+    assert!(allocs[0].args.contains(&("bytesize", "64".into())));
+    assert_eq!(allocs[0].result_name(), "CUDA_SUCCESS");
+    assert_eq!(allocs[1].result_name(), "CUDA_ERROR_OUT_OF_MEMORY");
+    assert!(events.iter().any(|e| e.function == "cuMemFree"));
+
+    let json = trace.to_chrome_json();
+    assert!(json.starts_with("{\"traceEvents\":[{\"name\":"));
+    assert!(json.contains("\"name\":\"cuMemAlloc\",\"cat\":\"cuda\",\"ph\":\"X\""));
+    assert!(json.contains("\"bytesize\":\"128\",\"result\":\"CUDA_ERROR_OUT_OF_MEMORY\"}"));
+}
+
+#[test]
+fn tracing_is_switched_at_runtime() {
+    let fake = FakeDriver::new();
+    let cuda = Arc::new(CUDA::with_driver(fake.clone()).unwrap());
+    assert_eq!(cuda.devices().count(), 1);
+    let device = Arc::new(Device::create(&cuda, 0).unwrap());
+    let first = Buffer::create(&device, 16).unwrap();
+
+    let trace = cuda.enable_trace();
+    assert!(Arc::ptr_eq(&trace, &cuda.enable_trace()));
+    let second = Buffer::create(&device, 32).unwrap();
+    let stopped = cuda.disable_trace().unwrap();
+    assert!(Arc::ptr_eq(&trace, &stopped));
+    drop((first, second));
+
+    let functions: Vec<_> = trace.events().iter().map(|e| e.function).collect();
+    assert_eq!(functions.iter().filter(|&&f| f == "cuMemAlloc").count(), 1);
+    assert!(!functions.contains(&"cuDeviceGetName"));
+    assert!(!functions.contains(&"cuMemFree"));
+}